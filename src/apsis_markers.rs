@@ -0,0 +1,123 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    physics::{Circle, DominantBody, GravityConfig, Mass, OrbitalElements, Velocity},
+    ship::Ship,
+};
+
+pub struct ApsisMarkersPlugin;
+
+impl Plugin for ApsisMarkersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_apsis_markers)
+            .add_systems(Update, apsis_markers_system);
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum ApsisMarker {
+    Periapsis,
+    Apoapsis,
+}
+
+impl ApsisMarker {
+    fn label(self) -> &'static str {
+        match self {
+            ApsisMarker::Periapsis => "Pe",
+            ApsisMarker::Apoapsis => "Ap",
+        }
+    }
+}
+
+const MARKER_RADIUS: f32 = 3.0;
+
+fn setup_apsis_markers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let mesh = meshes.add(Mesh::from(shape::Circle::new(MARKER_RADIUS)));
+    let material = materials.add(Color::rgb(0.9, 0.8, 0.3).into());
+    let font = asset_server.load("pixeboy.ttf");
+
+    for marker in [ApsisMarker::Periapsis, ApsisMarker::Apoapsis] {
+        commands
+            .spawn((
+                marker,
+                ColorMesh2dBundle {
+                    mesh: mesh.clone().into(),
+                    material: material.clone(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        marker.label(),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    text_anchor: Anchor::BottomCenter,
+                    transform: Transform::from_xyz(0.0, MARKER_RADIUS * 2.0, 1.0),
+                    ..default()
+                });
+            });
+    }
+}
+
+fn apsis_markers_system(
+    ship: Query<(&Transform, &Velocity, Option<&DominantBody>), With<Ship>>,
+    bodies: Query<(&Transform, &Mass, Option<&Velocity>, Option<&Circle>), Without<ApsisMarker>>,
+    camera: Query<&OrthographicProjection, With<Camera>>,
+    mut markers: Query<(&ApsisMarker, &mut Transform, &mut Visibility, &Children), Without<Ship>>,
+    mut texts: Query<&mut Text>,
+    config: Res<GravityConfig>,
+) {
+    let Ok((ship_transform, ship_velocity, dominant_body)) = ship.get_single() else {
+        return;
+    };
+    let scale = camera.single().scale;
+
+    let orbit = dominant_body
+        .and_then(|dominant_body| dominant_body.0)
+        .and_then(|body| bodies.get(body).ok())
+        .and_then(|(body_transform, body_mass, body_velocity, body_circle)| {
+            let body_position = body_transform.translation.truncate();
+            let relative_position = ship_transform.translation.truncate() - body_position;
+            let relative_velocity = ship_velocity.0 - body_velocity.map_or(Vec2::ZERO, |v| v.0);
+            let mu = config.gravitational_parameter(body_mass.0);
+            let elements = OrbitalElements::from_state(relative_position, relative_velocity, mu)?;
+            let radius = body_circle.map_or(0.0, |c| c.radius);
+            Some((body_position, radius, elements))
+        });
+
+    for (marker, mut transform, mut visibility, children) in markers.iter_mut() {
+        let apsis = orbit.and_then(|(body_position, radius, elements)| {
+            let (distance, relative_position) = match marker {
+                ApsisMarker::Periapsis => (elements.periapsis, elements.periapsis_position()),
+                ApsisMarker::Apoapsis => (elements.apoapsis?, elements.apoapsis_position()?),
+            };
+            Some((body_position + relative_position, distance - radius))
+        });
+
+        let Some((position, altitude)) = apsis else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        *visibility = Visibility::Visible;
+        // Keep the marker the same size on screen, regardless of zoom.
+        *transform =
+            Transform::from_translation(position.extend(2.0)).with_scale(Vec3::splat(scale));
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = format!("{} {:.0}", marker.label(), altitude);
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+
+use crate::physics::{Circle, Mass, PhysicsMaterial};
+
+/// A rock that is too small to pull on anything. Static asteroids are
+/// colliders, like planets. Drifting ones collide like the ship does, so it
+/// can push them around.
+#[derive(Component, Default)]
+pub struct Asteroid;
+
+#[derive(Bundle)]
+pub struct AsteroidBundle {
+    asteroid: Asteroid,
+    mass: Mass,
+    sprite: SpriteBundle,
+    circle: Circle,
+    material: PhysicsMaterial,
+}
+
+impl AsteroidBundle {
+    pub fn new(asset_server: &AssetServer, radius: f32, mass: f32, position: Vec2) -> Self {
+        let texture = asset_server.load("planet.png");
+        Self {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb(0.6, 0.55, 0.5),
+                    custom_size: Some(Vec2::splat(radius * 2.0)),
+                    ..default()
+                },
+                texture,
+                transform: Transform::from_xyz(position.x, position.y, 0.0),
+                ..default()
+            },
+            mass: Mass(mass),
+            asteroid: Asteroid,
+            circle: Circle { radius },
+            // Rough, and a bit bouncy.
+            material: PhysicsMaterial {
+                friction: 0.9,
+                restitution: 0.2,
+            },
+        }
+    }
+}
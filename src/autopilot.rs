@@ -0,0 +1,224 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    contact::Wrecked,
+    maneuver::PlannedManeuver,
+    physics::{DominantBody, PhysicsSet, Velocity},
+    physics_prediction::{PhysicsPrediction, Prediction},
+    ship::{apply_controls_system, thrust, Ship, ShipControls, MAX_VELOCITY_CHANGE},
+    time::TimeScale,
+};
+
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_autopilot_ui)
+            .add_systems(Update, (autopilot_input_system, autopilot_ui_system))
+            .add_systems(
+                FixedUpdate,
+                autopilot_system
+                    .before(apply_controls_system)
+                    .before(PhysicsSet::PhysicsSet),
+            );
+    }
+}
+
+/// Flies the ship by setting it's `ShipControls`, with the same limits the
+/// player has.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub enum Autopilot {
+    #[default]
+    Off,
+    /// Point along the velocity relative to the dominant body.
+    Prograde,
+    /// Point against the velocity relative to the dominant body.
+    Retrograde,
+    /// Point towards the dominant body.
+    RadialIn,
+    /// Point away from the dominant body.
+    RadialOut,
+    /// Point in a fixed direction.
+    TargetDirection(Vec2),
+    /// Change the velocity by `delta_v`, centered `time` seconds from now.
+    ExecuteBurn { delta_v: Vec2, time: f32 },
+}
+
+impl Autopilot {
+    fn name(&self) -> &'static str {
+        match self {
+            Autopilot::Off => "Off",
+            Autopilot::Prograde => "Prograde",
+            Autopilot::Retrograde => "Retrograde",
+            Autopilot::RadialIn => "Radial In",
+            Autopilot::RadialOut => "Radial Out",
+            Autopilot::TargetDirection(_) => "Target",
+            Autopilot::ExecuteBurn { .. } => "Burn",
+        }
+    }
+}
+
+/// How closely the ship has to point at the burn direction before thrusting.
+const BURN_ALIGNMENT_TOLERANCE: f32 = 0.05;
+/// The burn is done when less than this much delta-v is left.
+const BURN_DELTA_V_TOLERANCE: f32 = 0.01;
+
+#[derive(Component)]
+struct AutopilotText;
+
+fn setup_autopilot_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("pixeboy.ttf"),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_alignment(TextAlignment::Right)
+        .with_no_wrap()
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.),
+            right: Val::Px(5.),
+            ..default()
+        }),
+        AutopilotText,
+    ));
+}
+
+fn autopilot_input_system(
+    input: Res<Input<KeyCode>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    ship_prediction: Query<&Prediction, With<PhysicsPrediction>>,
+    maneuver: Res<PlannedManeuver>,
+    mut ships: Query<(&Transform, &mut Autopilot), (With<Ship>, Without<Wrecked>)>,
+) {
+    let Ok((ship_transform, mut autopilot)) = ships.get_single_mut() else {
+        return;
+    };
+
+    if input.just_pressed(KeyCode::X) {
+        *autopilot = Autopilot::Off;
+    } else if input.just_pressed(KeyCode::P) {
+        *autopilot = Autopilot::Prograde;
+    } else if input.just_pressed(KeyCode::R) {
+        *autopilot = Autopilot::Retrograde;
+    } else if input.just_pressed(KeyCode::J) {
+        *autopilot = Autopilot::RadialIn;
+    } else if input.just_pressed(KeyCode::K) {
+        *autopilot = Autopilot::RadialOut;
+    } else if input.just_pressed(KeyCode::T) {
+        // Point towards the mouse.
+        let (camera, camera_transform) = camera.single();
+        let cursor = window
+            .single()
+            .cursor_position()
+            .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor));
+        if let Some(cursor) = cursor {
+            let direction = cursor - ship_transform.translation.truncate();
+            if let Some(direction) = direction.try_normalize() {
+                *autopilot = Autopilot::TargetDirection(direction);
+            }
+        }
+    } else if input.just_pressed(KeyCode::B) {
+        // Execute the planned maneuver.
+        let burn = maneuver.0.and_then(|node| {
+            let point = node.point(ship_prediction.single())?;
+            Some(Autopilot::ExecuteBurn {
+                delta_v: node.world_delta_v(point.velocity),
+                time: node.time,
+            })
+        });
+        if let Some(burn) = burn {
+            *autopilot = burn;
+        }
+    }
+}
+
+pub fn autopilot_system(
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+    mut ships: Query<
+        (
+            &mut Autopilot,
+            &mut ShipControls,
+            &Transform,
+            &Velocity,
+            &Ship,
+            Option<&DominantBody>,
+        ),
+        Without<Wrecked>,
+    >,
+    bodies: Query<(&Transform, Option<&Velocity>), Without<Ship>>,
+    mut maneuver: ResMut<PlannedManeuver>,
+) {
+    let delta = time_scale.delta_f32(&time);
+    for (mut autopilot, mut controls, transform, velocity, ship, dominant_body) in ships.iter_mut()
+    {
+        // The ship's state relative to the body it's orbiting.
+        let (relative_position, relative_velocity) = dominant_body
+            .and_then(|dominant_body| bodies.get(dominant_body.0?).ok())
+            .map_or(
+                (transform.translation.truncate(), velocity.0),
+                |(body_transform, body_velocity)| {
+                    (
+                        transform.translation.truncate() - body_transform.translation.truncate(),
+                        velocity.0 - body_velocity.map_or(Vec2::ZERO, |v| v.0),
+                    )
+                },
+            );
+
+        let direction = match *autopilot {
+            Autopilot::Off => continue,
+            Autopilot::Prograde => relative_velocity.try_normalize(),
+            Autopilot::Retrograde => (-relative_velocity).try_normalize(),
+            Autopilot::RadialIn => (-relative_position).try_normalize(),
+            Autopilot::RadialOut => relative_position.try_normalize(),
+            Autopilot::TargetDirection(direction) => Some(direction),
+            Autopilot::ExecuteBurn {
+                ref mut delta_v,
+                ref mut time,
+            } => {
+                *time -= delta;
+                let remaining = delta_v.length();
+                let facing = transform.right().truncate();
+                let burn_duration = remaining / MAX_VELOCITY_CHANGE;
+                let aligned = facing.angle_between(*delta_v).abs() < BURN_ALIGNMENT_TOLERANCE;
+
+                // Start half way before the node, so the burn is centered on it.
+                let throttle = if aligned && *time <= burn_duration / 2.0 {
+                    (remaining / (MAX_VELOCITY_CHANGE * delta)).min(1.0)
+                } else {
+                    0.0
+                };
+                let (_, applied) = thrust(transform, ship, throttle, delta);
+                let direction = delta_v.try_normalize();
+                *delta_v -= applied;
+
+                if delta_v.length() < BURN_DELTA_V_TOLERANCE || ship.fuel <= 0.0 {
+                    // The planned node is done with too.
+                    maneuver.0 = None;
+                    *autopilot = Autopilot::Off;
+                    *controls = ShipControls::default();
+                    continue;
+                }
+                controls.throttle = throttle;
+                direction
+            }
+        };
+        controls.direction = direction;
+    }
+}
+
+fn autopilot_ui_system(
+    ships: Query<&Autopilot, (With<Ship>, Changed<Autopilot>)>,
+    mut text: Query<&mut Text, With<AutopilotText>>,
+) {
+    let Ok(autopilot) = ships.get_single() else {
+        return;
+    };
+    text.single_mut().sections[0].value = format!("Autopilot: {}", autopilot.name());
+}
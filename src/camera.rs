@@ -65,7 +65,9 @@ fn camera_track_target(
     mut query: Query<&mut Transform, With<CameraAnchor>>,
     target_query: Query<(&Transform, &CameraTarget), Without<CameraAnchor>>,
 ) {
-    let (target_tr, target) = target_query.single();
+    let Ok((target_tr, target)) = target_query.get_single() else {
+        return;
+    };
     let mut camera = query.single_mut();
 
     camera.translation = target_tr
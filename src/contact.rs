@@ -0,0 +1,305 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    autopilot::Autopilot,
+    physics::{
+        bounce_speed, collision_detection, collision_resolution, CollisionEvent, PhysicsMaterial,
+        PhysicsSet, Velocity,
+    },
+    ship::{Ship, ShipControls},
+};
+
+pub struct ContactPlugin;
+
+impl Plugin for ContactPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ContactEvent>().add_systems(
+            FixedUpdate,
+            (
+                classify_contacts
+                    .after(collision_detection)
+                    .before(collision_resolution),
+                apply_contact_outcomes.after(collision_resolution),
+            )
+                .in_set(PhysicsSet::PhysicsSet),
+        );
+    }
+}
+
+/// Touching down slower than this is a safe landing.
+const SAFE_LANDING_SPEED: f32 = 10.0;
+/// Touching down faster than this destroys the ship, whatever the angle.
+const CRASH_SPEED: f32 = 40.0;
+/// How far the ship's nose can be from pointing straight up, in radians,
+/// for it to land on it's legs instead of bouncing off.
+const MAX_LANDING_ANGLE: f32 = 0.35;
+/// How much hull a hard landing or a bounce takes away, per unit of speed
+/// above `SAFE_LANDING_SPEED`.
+const DAMAGE_PER_SPEED: f32 = 3.0;
+/// How much of the speed into the surface is kept when bouncing off, at
+/// least. Bouncier surfaces bounce harder.
+const BOUNCE_RESTITUTION: f32 = 0.5;
+
+/// What happened when a ship touched a surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContactOutcome {
+    Landed,
+    HardLanding { damage: f32 },
+    Crashed,
+    Bounced { damage: f32 },
+}
+
+/// Sent when a ship touches a surface it wasn't already resting on.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct ContactEvent {
+    pub ship: Entity,
+    pub body: Entity,
+    pub outcome: ContactOutcome,
+    /// The speed into the surface.
+    pub speed: f32,
+    /// The angle between the ship's nose and the surface normal, in radians.
+    pub angle: f32,
+    pub normal: Vec2,
+}
+
+/// The bodies a ship is resting on, so that staying on the ground isn't a new
+/// landing every tick. There can be more than one, like when the ship sits
+/// between two rocks.
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SurfaceContact(pub Vec<Entity>);
+
+/// A ship that crashed. It can't be controlled anymore.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Wrecked;
+
+pub fn classify_contact(speed: f32, angle: f32) -> ContactOutcome {
+    if speed >= CRASH_SPEED {
+        ContactOutcome::Crashed
+    } else if angle > MAX_LANDING_ANGLE {
+        ContactOutcome::Bounced {
+            damage: (speed - SAFE_LANDING_SPEED).max(0.0) * DAMAGE_PER_SPEED,
+        }
+    } else if speed <= SAFE_LANDING_SPEED {
+        ContactOutcome::Landed
+    } else {
+        ContactOutcome::HardLanding {
+            damage: (speed - SAFE_LANDING_SPEED) * DAMAGE_PER_SPEED,
+        }
+    }
+}
+
+/// Runs before `collision_resolution`, while the ship still has the velocity
+/// it hit the surface with.
+fn classify_contacts(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut ships: Query<(Entity, &Transform, &Velocity, Option<&mut SurfaceContact>), With<Ship>>,
+    bodies: Query<&Velocity, Without<Ship>>,
+    mut contact_events: EventWriter<ContactEvent>,
+) {
+    // The bodies each ship touched this tick.
+    let mut touching: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for event in collision_events.iter() {
+        let Ok((ship, transform, velocity, contact)) = ships.get(event.collision_entity) else {
+            continue;
+        };
+        let touched = touching.entry(ship).or_default();
+        if touched.contains(&event.collider_entity) {
+            continue;
+        }
+        touched.push(event.collider_entity);
+
+        let resting = contact.is_some_and(|contact| contact.0.contains(&event.collider_entity));
+        if resting {
+            continue;
+        }
+
+        // Collisions between moving bodies are already resolved, so they say
+        // how fast they hit.
+        let impact_velocity = event.impact_velocity.unwrap_or_else(|| {
+            let body_velocity = bodies
+                .get(event.collider_entity)
+                .map_or(Vec2::ZERO, |velocity| velocity.0);
+            velocity.0 - body_velocity
+        });
+        let speed = impact_velocity.dot(-event.normal).max(0.0);
+        let angle = transform
+            .right()
+            .truncate()
+            .angle_between(event.normal)
+            .abs();
+        contact_events.send(ContactEvent {
+            ship,
+            body: event.collider_entity,
+            outcome: classify_contact(speed, angle),
+            speed,
+            angle,
+            normal: event.normal,
+        });
+    }
+
+    // Remember what every ship touched this tick. Ships that touched nothing
+    // are in the air.
+    for (ship, _, _, contact) in ships.iter_mut() {
+        let touched = touching.remove(&ship).unwrap_or_default();
+        match contact {
+            Some(mut contact) => {
+                if contact.0 != touched {
+                    contact.0 = touched;
+                }
+            }
+            None => {
+                commands.entity(ship).insert(SurfaceContact(touched));
+            }
+        }
+    }
+}
+
+/// Runs after `collision_resolution` took away the speed into the surface.
+fn apply_contact_outcomes(
+    mut commands: Commands,
+    mut contact_events: EventReader<ContactEvent>,
+    mut ships: Query<(
+        &mut Ship,
+        &mut Velocity,
+        &mut Sprite,
+        &mut ShipControls,
+        &mut Autopilot,
+    )>,
+    materials: Query<&PhysicsMaterial>,
+) {
+    for event in contact_events.iter() {
+        let Ok((mut ship, mut velocity, mut sprite, mut controls, mut autopilot)) =
+            ships.get_mut(event.ship)
+        else {
+            continue;
+        };
+        info!("Contact: {:?} at {:.1}", event.outcome, event.speed);
+
+        match event.outcome {
+            ContactOutcome::Landed => {}
+            ContactOutcome::HardLanding { damage } => {
+                ship.hull = (ship.hull - damage).max(0.0);
+            }
+            ContactOutcome::Crashed => {
+                ship.hull = 0.0;
+            }
+            ContactOutcome::Bounced { damage } => {
+                ship.hull = (ship.hull - damage).max(0.0);
+
+                // `collision_resolution` already bounced us off as much as the
+                // surface's material does, so only add what is missing.
+                let restitution = materials
+                    .get(event.body)
+                    .map_or(0.0, |material| material.restitution);
+                let bounced = bounce_speed(event.speed, restitution);
+                velocity.0 += event.normal * (event.speed * BOUNCE_RESTITUTION - bounced).max(0.0);
+            }
+        }
+
+        if ship.hull <= 0.0 {
+            // Let go of everything, so nothing keeps thrusting or turning.
+            commands.entity(event.ship).insert(Wrecked);
+            *controls = ShipControls::default();
+            *autopilot = Autopilot::Off;
+            sprite.color = Color::rgb(0.4, 0.2, 0.2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        physics::{
+            broad_phase_system, dynamic_collision_system, BroadPhase, Circle, Collider, Collision,
+        },
+        ship::MAX_HULL,
+    };
+
+    /// A ship with everything the contact systems need.
+    fn spawn_ship(world: &mut World, position: Vec2, velocity: Vec2) -> Entity {
+        world
+            .spawn((
+                Ship::default(),
+                Transform::from_translation(position.extend(0.0)),
+                Velocity(velocity),
+                Circle { radius: 5.0 },
+                Collision,
+                Sprite::default(),
+                ShipControls::default(),
+                Autopilot::Off,
+            ))
+            .id()
+    }
+
+    #[test]
+    fn bounced_ship_moves_away_from_the_surface() {
+        let mut world = World::new();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<Events<ContactEvent>>();
+        let planet = world
+            .spawn((
+                Transform::default(),
+                Circle { radius: 100.0 },
+                Collider,
+                PhysicsMaterial::default(),
+            ))
+            .id();
+        // Coming down fast on it's side, with the nose along the surface.
+        let ship = spawn_ship(&mut world, Vec2::new(0.0, 105.0), Vec2::new(0.0, -30.0));
+        world.send_event(CollisionEvent {
+            collision_entity: ship,
+            collider_entity: planet,
+            normal: Vec2::Y,
+            point: Vec2::new(0.0, 100.0),
+            penetration: None,
+            impact_velocity: None,
+        });
+
+        let mut schedule = Schedule::new();
+        schedule.add_systems(
+            (classify_contacts, collision_resolution, apply_contact_outcomes).chain(),
+        );
+        schedule.run(&mut world);
+
+        let contact = world.resource::<Events<ContactEvent>>();
+        let outcome = contact.iter_current_update_events().next().unwrap().outcome;
+        assert!(matches!(outcome, ContactOutcome::Bounced { .. }), "{outcome:?}");
+        let velocity = world.get::<Velocity>(ship).unwrap().0;
+        assert!(velocity.y > 0.0, "{velocity:?}");
+        let hull = world.get::<Ship>(ship).unwrap().hull;
+        assert!(hull < MAX_HULL && hull > 0.0, "{hull}");
+    }
+
+    #[test]
+    fn ramming_an_asteroid_crashes_the_ship() {
+        let mut world = World::new();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<Events<ContactEvent>>();
+        world.init_resource::<BroadPhase>();
+        let ship = spawn_ship(&mut world, Vec2::ZERO, Vec2::new(60.0, 0.0));
+        world.spawn((
+            Transform::from_xyz(8.0, 0.0, 0.0),
+            Velocity(Vec2::new(-10.0, 0.0)),
+            Circle { radius: 5.0 },
+            Collision,
+        ));
+
+        let mut schedule = Schedule::new();
+        schedule.add_systems(
+            (
+                broad_phase_system,
+                dynamic_collision_system,
+                classify_contacts,
+                collision_resolution,
+                apply_contact_outcomes,
+            )
+                .chain(),
+        );
+        schedule.run(&mut world);
+
+        assert_eq!(world.get::<Ship>(ship).unwrap().hull, 0.0);
+        assert!(world.get::<Wrecked>(ship).is_some());
+    }
+}
@@ -0,0 +1,74 @@
+use bevy::{math::DVec2, prelude::*};
+
+use crate::{
+    autopilot::autopilot_system, camera::CameraTarget, physics::PhysicsSet,
+    ship::apply_controls_system,
+};
+
+pub struct FloatingOriginPlugin;
+
+impl Plugin for FloatingOriginPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FloatingOrigin>()
+            .add_event::<OriginShifted>()
+            // First, so every other fixed step system sees the same origin.
+            .add_systems(
+                FixedUpdate,
+                rebase_origin
+                    .before(autopilot_system)
+                    .before(apply_controls_system)
+                    .before(PhysicsSet::PhysicsSet),
+            );
+    }
+}
+
+/// Where the origin of the `Transform`s is, in absolute world coordinates.
+///
+/// Everything is kept close to the origin so `f32` positions stay precise,
+/// even in huge levels. When the camera target gets too far away, every root
+/// entity is moved back, and the origin is moved forward to match.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct FloatingOrigin(pub DVec2);
+
+impl FloatingOrigin {
+    /// Converts an absolute world position to a position relative to the origin.
+    pub fn to_local(self, absolute: DVec2) -> Vec2 {
+        (absolute - self.0).as_vec2()
+    }
+}
+
+/// Sent after every root entity was moved by `-offset`. Anything that keeps
+/// positions outside of `Transform`s needs to move them too.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct OriginShifted {
+    pub offset: Vec2,
+}
+
+/// How far the camera target can get from the origin before everything is
+/// moved back.
+const REBASE_DISTANCE: f32 = 5000.0;
+
+fn rebase_origin(
+    target: Query<Entity, With<CameraTarget>>,
+    mut roots: Query<&mut Transform, (Without<Parent>, Without<Node>)>,
+    mut origin: ResMut<FloatingOrigin>,
+    mut events: EventWriter<OriginShifted>,
+) {
+    let Some(offset) = target
+        .get_single()
+        .ok()
+        .and_then(|target| roots.get(target).ok())
+        .map(|transform| transform.translation.truncate())
+    else {
+        return;
+    };
+    if offset.length() < REBASE_DISTANCE {
+        return;
+    }
+
+    for mut transform in roots.iter_mut() {
+        transform.translation -= offset.extend(0.0);
+    }
+    origin.0 += offset.as_dvec2();
+    events.send(OriginShifted { offset });
+}
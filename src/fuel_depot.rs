@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+
+use crate::{
+    contact::Wrecked,
+    physics::{PhysicsSet, Velocity},
+    ship::Ship,
+    time::TimeScale,
+};
+
+pub struct FuelDepotPlugin;
+
+impl Plugin for FuelDepotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, refuel_system.after(PhysicsSet::PhysicsSet));
+    }
+}
+
+/// How much fuel per second a depot gives, if the level doesn't say.
+pub const DEFAULT_REFUEL_RATE: f32 = 10.0;
+/// Ships have to slow down to at most this speed to refuel.
+const MAX_REFUEL_SPEED: f32 = 5.0;
+
+/// A place in space where ships that hold still refuel.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct FuelDepot {
+    /// How close ships have to be.
+    pub radius: f32,
+    /// How much fuel per second ships get.
+    pub rate: f32,
+}
+
+#[derive(Bundle)]
+pub struct FuelDepotBundle {
+    depot: FuelDepot,
+    sprite: SpriteBundle,
+}
+
+impl FuelDepotBundle {
+    pub fn new(asset_server: &AssetServer, position: Vec2, radius: f32, rate: f32) -> Self {
+        let texture = asset_server.load("planet.png");
+        Self {
+            depot: FuelDepot { radius, rate },
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(1.0, 0.8, 0.2, 0.3),
+                    custom_size: Some(Vec2::splat(radius * 2.0)),
+                    ..default()
+                },
+                texture,
+                // Behind everything that collides.
+                transform: Transform::from_xyz(position.x, position.y, -1.0),
+                ..default()
+            },
+        }
+    }
+}
+
+fn refuel_system(
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+    depots: Query<(&FuelDepot, &Transform)>,
+    mut ships: Query<(&mut Ship, &Transform, &Velocity), Without<Wrecked>>,
+) {
+    let delta = time_scale.delta_f32(&time);
+    for (mut ship, ship_transform, velocity) in ships.iter_mut() {
+        if velocity.0.length() > MAX_REFUEL_SPEED {
+            continue;
+        }
+        let position = ship_transform.translation.truncate();
+        let rate = depots
+            .iter()
+            .filter(|(depot, transform)| {
+                transform.translation.truncate().distance(position) <= depot.radius
+            })
+            .map(|(depot, _)| depot.rate)
+            .fold(0.0, f32::max);
+        ship.fuel = (ship.fuel + rate * delta).min(ship.max_fuel);
+    }
+}
@@ -121,7 +121,9 @@ fn fuelbar_system(
     ship_query: Query<&Ship>,
     mut fuelbar_query: Query<&mut Style, With<FuelBar>>,
 ) {
-    let ship = ship_query.single();
+    let Ok(ship) = ship_query.get_single() else {
+        return;
+    };
     let mut fuelbar_style = fuelbar_query.single_mut();
 
     fuelbar_style.width = Val::Vw(FILL_WIDTH_VW * ship.fuel / ship.max_fuel);
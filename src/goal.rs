@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use crate::{contact::Wrecked, physics::PhysicsSet, ship::Ship};
+
+pub struct GoalPlugin;
+
+impl Plugin for GoalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GoalReachedEvent>()
+            .add_systems(FixedUpdate, goal_system.after(PhysicsSet::PhysicsSet));
+    }
+}
+
+/// A zone the ship has to get to.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Goal {
+    pub radius: f32,
+    pub reached: bool,
+}
+
+/// Sent the first time a ship gets in a goal zone.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GoalReachedEvent {
+    pub ship: Entity,
+    pub goal: Entity,
+}
+
+#[derive(Bundle)]
+pub struct GoalBundle {
+    goal: Goal,
+    sprite: SpriteBundle,
+}
+
+impl GoalBundle {
+    pub fn new(asset_server: &AssetServer, position: Vec2, radius: f32) -> Self {
+        let texture = asset_server.load("planet.png");
+        Self {
+            goal: Goal {
+                radius,
+                reached: false,
+            },
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.2, 1.0, 0.4, 0.2),
+                    custom_size: Some(Vec2::splat(radius * 2.0)),
+                    ..default()
+                },
+                texture,
+                // Behind everything that collides.
+                transform: Transform::from_xyz(position.x, position.y, -1.0),
+                ..default()
+            },
+        }
+    }
+}
+
+fn goal_system(
+    mut goals: Query<(Entity, &mut Goal, &Transform, &mut Sprite)>,
+    ships: Query<(Entity, &Transform), (With<Ship>, Without<Wrecked>)>,
+    mut events: EventWriter<GoalReachedEvent>,
+) {
+    for (goal_entity, mut goal, transform, mut sprite) in goals.iter_mut() {
+        if goal.reached {
+            continue;
+        }
+        let center = transform.translation.truncate();
+        let Some((ship, _)) = ships.iter().find(|(_, ship_transform)| {
+            ship_transform.translation.truncate().distance(center) <= goal.radius
+        }) else {
+            continue;
+        };
+
+        info!("Goal reached");
+        goal.reached = true;
+        sprite.color.set_a(0.5);
+        events.send(GoalReachedEvent {
+            ship,
+            goal: goal_entity,
+        });
+    }
+}
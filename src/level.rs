@@ -1,29 +1,139 @@
 mod loader;
+mod ron_loader;
 
 use bevy::{
+    math::DVec2,
     prelude::*,
     reflect::{TypePath, TypeUuid},
 };
 
+use crate::{
+    floating_origin::FloatingOrigin,
+    physics::{
+        AffectedByGravity, Atmosphere, Circle, Collider, Collision, GravityConfig, Orbit, Shape,
+        Velocity,
+    },
+    ship::spawn_ship,
+    time::TimeScale,
+};
+
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         app.add_asset::<LevelAsset>()
             .add_asset_loader(loader::LevelAssetLoader)
-            .add_systems(Update, (spawn_entities, listen_for_level_loading));
+            .add_asset_loader(ron_loader::RonLevelAssetLoader)
+            .add_event::<LevelEvent>()
+            .add_event::<LevelUnloadedEvent>()
+            .add_systems(
+                Update,
+                (
+                    level_event_system.before(spawn_entities),
+                    spawn_entities,
+                    listen_for_level_loading,
+                ),
+            );
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum LevelAssetObject {
-    Planet { position: Vec2, radius: f32 },
+    /// A planet. If it has a velocity, it moves by gravity (n-body), and
+    /// otherwise it stays in place.
+    Planet {
+        position: DVec2,
+        radius: f32,
+        velocity: Option<Vec2>,
+        properties: ObjectProperties,
+    },
+    /// A planet that orbits another object on rails.
+    Moon {
+        /// The index of the object this orbits. Must come before this one.
+        parent: usize,
+        radius: f32,
+        semi_major_axis: f32,
+        eccentricity: f32,
+        /// The initial mean anomaly, in degrees.
+        phase: f32,
+        properties: ObjectProperties,
+    },
+    /// A rock that doesn't pull on anything. If it has a velocity, it drifts
+    /// and the ship can push it around, and otherwise it stays in place.
+    Asteroid {
+        position: DVec2,
+        radius: f32,
+        velocity: Option<Vec2>,
+        properties: ObjectProperties,
+    },
+    /// A platform that refuels and repairs ships that land on it. If it has a
+    /// velocity, it moves by gravity.
+    Station {
+        position: DVec2,
+        velocity: Option<Vec2>,
+    },
+    /// A zone that refuels ships that hold still in it.
+    FuelDepot {
+        position: DVec2,
+        radius: f32,
+        /// Fuel per second.
+        rate: f32,
+    },
+    /// A zone the ship has to get to.
+    Goal { position: DVec2, radius: f32 },
+    /// Where the player's ship starts. A level has exactly one.
+    ShipSpawn {
+        position: DVec2,
+        velocity: Vec2,
+        /// Which way the nose points, in degrees counter-clockwise from +x.
+        rotation: f32,
+        /// A full tank if not set.
+        fuel: Option<f32>,
+    },
+}
+
+impl LevelAssetObject {
+    /// The properties of a planet, moon or asteroid. Other objects don't have
+    /// any.
+    pub fn properties_mut(&mut self) -> Option<&mut ObjectProperties> {
+        match self {
+            LevelAssetObject::Planet { properties, .. }
+            | LevelAssetObject::Moon { properties, .. }
+            | LevelAssetObject::Asteroid { properties, .. } => Some(properties),
+            LevelAssetObject::Station { .. }
+            | LevelAssetObject::FuelDepot { .. }
+            | LevelAssetObject::Goal { .. }
+            | LevelAssetObject::ShipSpawn { .. } => None,
+        }
+    }
+
+    /// Can a moon orbit this?
+    pub fn is_body(&self) -> bool {
+        matches!(
+            self,
+            LevelAssetObject::Planet { .. } | LevelAssetObject::Moon { .. }
+        )
+    }
+}
+
+/// The optional parts of an object, which the lines after it in the level
+/// file set.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ObjectProperties {
+    /// Only the structured level format has names.
+    pub name: Option<String>,
+    pub atmosphere: Option<Atmosphere>,
+    /// The shape to collide with, if it isn't a circle of the object's radius.
+    pub hull: Option<Shape>,
+    /// Overrides the level's `GravityConfig::density`.
+    pub density: Option<f32>,
 }
 
 #[derive(Debug, TypeUuid, TypePath)]
 #[uuid = "209cad4e-c5fd-48ed-b16e-567adf098ed2"]
 pub struct LevelAsset {
     pub objects: Vec<LevelAssetObject>,
+    pub gravity: GravityConfig,
 }
 
 #[derive(Component, Debug, Default)]
@@ -35,51 +145,239 @@ pub struct Level {
     pub objects: Vec<Entity>,
 }
 
+/// Send to change the level.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub enum LevelEvent {
+    /// Unloads the current level, if there is one, and loads the level at
+    /// this asset path.
+    Load(String),
+    /// Despawns everything in the current level, including the ship, and puts
+    /// back what the level changed.
+    Unload,
+}
+
+/// Sent when a level was unloaded, for anything that keeps state about it.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelUnloadedEvent;
+
 #[derive(Component)]
 pub struct LevelAssetLoaded;
 
 #[derive(Component)]
 pub struct LevelDoneLoading;
 
+fn level_event_system(
+    mut events: EventReader<LevelEvent>,
+    levels: Query<Entity, Or<(With<Level>, With<LevelObject>)>>,
+    asset_server: Res<AssetServer>,
+    mut time_scale: ResMut<TimeScale>,
+    mut unloaded: EventWriter<LevelUnloadedEvent>,
+    mut commands: Commands,
+) {
+    // Only the last one matters, since loading unloads first anyway.
+    let Some(event) = events.iter().last() else {
+        return;
+    };
+
+    // The ship is part of the level, so the next level spawns a new one with
+    // a new tank of fuel.
+    for entity in levels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.insert_resource(GravityConfig::default());
+    *time_scale = TimeScale::default();
+    unloaded.send(LevelUnloadedEvent);
+
+    if let LevelEvent::Load(path) = event {
+        info!("Loading level {path}");
+        commands.spawn(Level {
+            level_asset: asset_server.load(path.as_str()),
+            ..default()
+        });
+    }
+}
+
 fn spawn_entities(
     mut levels: Query<(Entity, &mut Level), Without<LevelAssetLoaded>>,
     asset_server: Res<AssetServer>,
     level_assets: Res<Assets<LevelAsset>>,
+    origin: Res<FloatingOrigin>,
     mut commands: Commands,
 ) {
     for (entity, mut level) in levels.iter_mut() {
         if let Some(level_asset) = level_assets.get(&level.level_asset) {
             // The asset has finished loading!
             commands.entity(entity).insert(LevelAssetLoaded);
+            commands.insert_resource(level_asset.gravity);
             // Now load the objects.
-            level.objects = spawn_level_objects(level_asset, &asset_server, &mut commands);
+            level.objects =
+                spawn_level_objects(level_asset, &origin, &asset_server, &mut commands);
         }
     }
 }
 
 fn spawn_level_objects(
     level_asset: &LevelAsset,
+    origin: &FloatingOrigin,
     asset_server: &AssetServer,
     commands: &mut Commands,
 ) -> Vec<Entity> {
     let mut ret = vec![];
-    for object in &level_asset.objects {
-        ret.push(spawn_object(object, asset_server, commands));
+    for (index, object) in level_asset.objects.iter().enumerate() {
+        // Level files are in absolute coordinates.
+        let position = origin.to_local(object_position(&level_asset.objects, index));
+        ret.push(spawn_object(
+            object,
+            position,
+            &level_asset.gravity,
+            &ret,
+            asset_server,
+            commands,
+        ));
     }
     ret
 }
 
 fn spawn_object(
     object: &LevelAssetObject,
+    position: Vec2,
+    gravity: &GravityConfig,
+    spawned: &[Entity],
     asset_server: &AssetServer,
     commands: &mut Commands,
 ) -> Entity {
-    use crate::planet::PlanetBundle;
+    use crate::{
+        asteroid::AsteroidBundle, fuel_depot::FuelDepotBundle, goal::GoalBundle,
+        planet::PlanetBundle, station::StationBundle,
+    };
+
+    let planet = |radius: f32, properties: &ObjectProperties| {
+        let mass = gravity.mass(radius, properties.density);
+        PlanetBundle::new(asset_server, radius, mass, position)
+    };
+    let (mut entity, radius, properties) = match object {
+        LevelAssetObject::Planet {
+            radius,
+            velocity,
+            properties,
+            ..
+        } => {
+            let planet = (LevelObject, planet(*radius, properties));
+            let entity = match *velocity {
+                None => commands.spawn(planet),
+                Some(velocity) => commands.spawn((planet, Velocity(velocity), AffectedByGravity)),
+            };
+            (entity, *radius, properties)
+        }
+        &LevelAssetObject::Moon {
+            parent,
+            radius,
+            semi_major_axis,
+            eccentricity,
+            phase,
+            ref properties,
+        } => {
+            let entity = commands.spawn((
+                LevelObject,
+                planet(radius, properties),
+                Velocity::default(),
+                moon_orbit(spawned[parent], semi_major_axis, eccentricity, phase),
+            ));
+            (entity, radius, properties)
+        }
+        LevelAssetObject::Asteroid {
+            radius,
+            velocity,
+            properties,
+            ..
+        } => {
+            let mass = gravity.mass(*radius, properties.density);
+            let asteroid = (
+                LevelObject,
+                AsteroidBundle::new(asset_server, *radius, mass, position),
+            );
+            let entity = match *velocity {
+                None => commands.spawn((asteroid, Collider)),
+                Some(velocity) => commands.spawn((
+                    asteroid,
+                    Collision,
+                    Velocity(velocity),
+                    AffectedByGravity,
+                )),
+            };
+            (entity, *radius, properties)
+        }
+        &LevelAssetObject::Station { velocity, .. } => {
+            let mut entity = commands.spawn((LevelObject, StationBundle::new(position)));
+            if let Some(velocity) = velocity {
+                entity.insert((Velocity(velocity), AffectedByGravity));
+            }
+            return entity.id();
+        }
+        &LevelAssetObject::FuelDepot { radius, rate, .. } => {
+            let depot = FuelDepotBundle::new(asset_server, position, radius, rate);
+            return commands.spawn((LevelObject, depot)).id();
+        }
+        &LevelAssetObject::Goal { radius, .. } => {
+            let goal = GoalBundle::new(asset_server, position, radius);
+            return commands.spawn((LevelObject, goal)).id();
+        }
+        &LevelAssetObject::ShipSpawn {
+            velocity,
+            rotation,
+            fuel,
+            ..
+        } => {
+            let transform = Transform::from_translation(position.extend(0.0))
+                .with_rotation(Quat::from_rotation_z(rotation.to_radians()));
+            let ship = spawn_ship(commands, asset_server, transform, velocity, fuel);
+            commands.entity(ship).insert(LevelObject);
+            return ship;
+        }
+    };
+
+    if let Some(name) = &properties.name {
+        entity.insert(Name::new(name.clone()));
+    }
+    if let Some(atmosphere) = properties.atmosphere {
+        entity.insert(atmosphere);
+    }
+    if let Some(hull) = &properties.hull {
+        // The circle still has to hold the whole hull, for the broad phase.
+        let radius = radius.max(hull.bounding_radius());
+        entity.insert((hull.clone(), Circle { radius }));
+    }
+    entity.id()
+}
+
+/// Where an object starts, in world space.
+fn object_position(objects: &[LevelAssetObject], index: usize) -> DVec2 {
+    match objects[index] {
+        LevelAssetObject::Planet { position, .. } => position,
+        LevelAssetObject::Moon {
+            parent,
+            semi_major_axis,
+            eccentricity,
+            phase,
+            ..
+        } => {
+            let orbit = moon_orbit(Entity::PLACEHOLDER, semi_major_axis, eccentricity, phase);
+            object_position(objects, parent) + orbit.relative_position().as_dvec2()
+        }
+        LevelAssetObject::Asteroid { position, .. }
+        | LevelAssetObject::Station { position, .. }
+        | LevelAssetObject::FuelDepot { position, .. }
+        | LevelAssetObject::Goal { position, .. }
+        | LevelAssetObject::ShipSpawn { position, .. } => position,
+    }
+}
 
-    match object {
-        LevelAssetObject::Planet { position, radius } => commands
-            .spawn((LevelObject, PlanetBundle::new(asset_server, *radius, *position)))
-            .id(),
+fn moon_orbit(parent: Entity, semi_major_axis: f32, eccentricity: f32, phase: f32) -> Orbit {
+    Orbit {
+        parent,
+        semi_major_axis,
+        eccentricity,
+        mean_anomaly: phase.to_radians(),
     }
 }
 
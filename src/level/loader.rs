@@ -1,9 +1,16 @@
+use std::{fmt, str::FromStr};
+
 use bevy::{
     asset::{AssetLoader, LoadedAsset, Error},
+    math::DVec2,
     prelude::*,
 };
 
-use crate::level::LevelAssetObject;
+use crate::{
+    fuel_depot::DEFAULT_REFUEL_RATE,
+    level::{LevelAssetObject, ObjectProperties},
+    physics::{Atmosphere, GravityConfig, Shape},
+};
 
 use super::LevelAsset;
 
@@ -24,35 +31,736 @@ impl AssetLoader for LevelAssetLoader {
     }
 
     fn extensions(&self) -> &[&str] {
-        &["txt"]
+        &["level.txt"]
     }
 }
 
-fn parse_level(source: &str) -> Result<LevelAsset, Error> {
-    let objects = source
-        .lines()
-        .map(parse_line)
-        .collect::<Result<Vec<_>, _>>()?;
+/// Where and why a level file couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Starting from 1.
+    pub line: usize,
+    /// In characters, starting from 1.
+    pub column: usize,
+    /// The word that was wrong. Empty if the line ended too early.
+    pub found: String,
+    pub expected: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: expected {}, ", self.line, self.column, self.expected)?;
+        if self.found.is_empty() {
+            write!(f, "found the end of the line")
+        } else {
+            write!(f, "found {:?}", self.found)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A line of a level file. Objects are numbered by the order they appear in,
+/// and modifiers change the object before them.
+enum Line {
+    Object(LevelAssetObject),
+    Atmosphere(Atmosphere),
+    Hull(Shape),
+    Density(f32),
+    /// Changes the level's gravity, wherever it is.
+    Gravity(GravityParameter, f32),
+}
 
-    Ok(LevelAsset { objects })
+enum GravityParameter {
+    Constant,
+    Softening,
+    Density,
+    Falloff,
 }
 
-fn parse_line(line: &str) -> Result<LevelAssetObject, Error> {
-    let line = line.trim();
-    if !line.starts_with("Planet ") {
-        return Err(Error::msg("Invalid line"));
+/// Parses a level, one line at a time. Blank lines are skipped, and `#`
+/// starts a comment that goes to the end of the line. There has to be a
+/// `Ship` line, so the ship never starts somewhere arbitrary, like inside a
+/// planet.
+fn parse_level(source: &str) -> Result<LevelAsset, ParseError> {
+    let mut objects: Vec<LevelAssetObject> = Vec::new();
+    let mut gravity = GravityConfig::default();
+    for (index, line) in source.lines().enumerate() {
+        let words = Words::new(index + 1, line);
+        if words.is_empty() {
+            continue;
+        }
+
+        let line = parse_line(&words, &objects)?;
+        match line {
+            Line::Atmosphere(atmosphere) => {
+                last_properties(&mut objects, &words)?.atmosphere = Some(atmosphere);
+            }
+            Line::Hull(hull) => last_properties(&mut objects, &words)?.hull = Some(hull),
+            Line::Density(density) => {
+                last_properties(&mut objects, &words)?.density = Some(density);
+            }
+            Line::Object(object) => objects.push(object),
+            Line::Gravity(parameter, value) => match parameter {
+                GravityParameter::Constant => gravity.gravitational_constant = value,
+                GravityParameter::Softening => gravity.softening = value,
+                GravityParameter::Density => gravity.density = value,
+                GravityParameter::Falloff => gravity.falloff = value,
+            },
+        }
+    }
+
+    let has_ship = objects
+        .iter()
+        .any(|object| matches!(object, LevelAssetObject::ShipSpawn { .. }));
+    if !has_ship {
+        return Err(ParseError {
+            line: source.lines().count() + 1,
+            column: 1,
+            found: String::new(),
+            expected: "a `Ship` line somewhere in the level".to_string(),
+        });
+    }
+
+    Ok(LevelAsset { objects, gravity })
+}
+
+/// The properties of the last object, for the modifier on `words` to change.
+fn last_properties<'a>(
+    objects: &'a mut [LevelAssetObject],
+    words: &Words,
+) -> Result<&'a mut ObjectProperties, ParseError> {
+    objects
+        .last_mut()
+        .and_then(LevelAssetObject::properties_mut)
+        .ok_or_else(|| words.error(0, "a planet, moon or asteroid before this line"))
+}
+
+/// The words of one line, with the columns they start at.
+struct Words<'a> {
+    line: usize,
+    words: Vec<(usize, &'a str)>,
+    /// The column just after the last word, for errors about missing words.
+    end: usize,
+}
+
+impl<'a> Words<'a> {
+    fn new(line: usize, text: &'a str) -> Self {
+        let text = text.split('#').next().unwrap_or_default();
+        let mut words = Vec::new();
+        let mut start = None;
+        for (column, (byte, character)) in (1..).zip(text.char_indices()) {
+            match (character.is_whitespace(), start) {
+                (false, None) => start = Some((column, byte)),
+                (true, Some((start_column, start_byte))) => {
+                    words.push((start_column, &text[start_byte..byte]));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some((start_column, start_byte)) = start {
+            words.push((start_column, &text[start_byte..]));
+        }
+        let end = words
+            .last()
+            .map_or(1, |&(column, word)| column + word.chars().count());
+
+        Self { line, words, end }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&'a str> {
+        self.words.get(index).map(|&(_, word)| word)
+    }
+
+    /// An error at the word `index`, or at the end of the line if there
+    /// aren't that many words.
+    fn error(&self, index: usize, expected: impl Into<String>) -> ParseError {
+        let (column, found) = self
+            .words
+            .get(index)
+            .map_or((self.end, ""), |&(column, word)| (column, word));
+        ParseError {
+            line: self.line,
+            column,
+            found: found.to_string(),
+            expected: expected.into(),
+        }
+    }
+
+    fn parse<T: FromStr>(&self, index: usize, expected: &str) -> Result<T, ParseError> {
+        self.get(index)
+            .and_then(|word| word.parse().ok())
+            .ok_or_else(|| self.error(index, expected))
+    }
+
+    /// Fails if the line doesn't have one of the given numbers of words.
+    fn expect_len(&self, lengths: &[usize], usage: &str) -> Result<(), ParseError> {
+        if lengths.contains(&self.len()) {
+            return Ok(());
+        }
+        // Point at the first word that is missing or extra.
+        let index = lengths
+            .iter()
+            .copied()
+            .find(|&length| length > self.len())
+            .unwrap_or_else(|| lengths.iter().copied().max().unwrap_or_default());
+        let expected = if index >= self.len() {
+            format!("more words, like `{usage}`")
+        } else {
+            format!("the end of the line, like `{usage}`")
+        };
+        Err(self.error(index, expected))
     }
+}
 
-    let parts = line.split(' ').collect::<Vec<_>>();
-    if parts.len() != 4 {
-        return Err(Error::msg("Invalid line"));
+/// Parses one line, given the objects on the lines before it.
+fn parse_line(words: &Words, objects: &[LevelAssetObject]) -> Result<Line, ParseError> {
+    match words.get(0).unwrap_or_default() {
+        "Planet" => parse_planet(words).map(Line::Object),
+        "Moon" => parse_moon(words, objects).map(Line::Object),
+        "Asteroid" => parse_asteroid(words).map(Line::Object),
+        "Station" => parse_station(words).map(Line::Object),
+        "FuelDepot" => parse_fuel_depot(words).map(Line::Object),
+        "Goal" => parse_goal(words).map(Line::Object),
+        "Ship" => parse_ship(words, objects).map(Line::Object),
+        "Atmosphere" => parse_atmosphere(words).map(Line::Atmosphere),
+        "Hull" => parse_hull(words).map(Line::Hull),
+        "Density" => parse_density(words).map(Line::Density),
+        "Gravity" => parse_gravity(words),
+        _ => Err(words.error(
+            0,
+            "an object like `Planet` or `Ship`, or a modifier like `Hull` or `Gravity`",
+        )),
     }
+}
+
+/// `Planet x y radius`, or `Planet x y radius vx vy` for a planet that moves.
+fn parse_planet(words: &Words) -> Result<LevelAssetObject, ParseError> {
+    words.expect_len(&[4, 6], "Planet x y radius [vx vy]")?;
 
-    let x = parts[1].parse::<f32>()?;
-    let y = parts[2].parse::<f32>()?;
-    let radius = parts[3].parse::<f32>()?;
+    let x = words.parse(1, "the x position")?;
+    let y = words.parse(2, "the y position")?;
+    let radius = words.parse(3, "the radius")?;
+    let velocity = if words.len() == 6 {
+        let vx = words.parse(4, "the x velocity")?;
+        let vy = words.parse(5, "the y velocity")?;
+        Some(Vec2::new(vx, vy))
+    } else {
+        None
+    };
+    if radius <= 0.0 {
+        return Err(words.error(3, "a positive radius"));
+    }
     Ok(LevelAssetObject::Planet {
         radius,
-        position: Vec2::new(x, y),
+        position: DVec2::new(x, y),
+        velocity,
+        properties: ObjectProperties::default(),
     })
 }
+
+/// `Moon parent radius semi-major-axis eccentricity phase`, where `parent` is
+/// the index of an earlier object and `phase` is in degrees.
+fn parse_moon(words: &Words, objects: &[LevelAssetObject]) -> Result<LevelAssetObject, ParseError> {
+    words.expect_len(&[6], "Moon parent radius semi-major-axis eccentricity phase")?;
+
+    let parent = words.parse(1, "the index of the parent")?;
+    let radius = words.parse(2, "the radius")?;
+    let semi_major_axis = words.parse(3, "the semi-major axis")?;
+    let eccentricity = words.parse(4, "the eccentricity")?;
+    let phase = words.parse(5, "the phase, in degrees")?;
+    if !objects.get(parent).is_some_and(LevelAssetObject::is_body) {
+        return Err(words.error(1, "the index of a planet or moon before this one"));
+    }
+    if radius <= 0.0 {
+        return Err(words.error(2, "a positive radius"));
+    }
+    if semi_major_axis <= 0.0 {
+        return Err(words.error(3, "a positive semi-major axis"));
+    }
+    if !(0.0..1.0).contains(&eccentricity) {
+        return Err(words.error(4, "an eccentricity in [0, 1)"));
+    }
+    Ok(LevelAssetObject::Moon {
+        parent,
+        radius,
+        semi_major_axis,
+        eccentricity,
+        phase,
+        properties: ObjectProperties::default(),
+    })
+}
+
+/// `Asteroid x y radius`, or `Asteroid x y radius vx vy` for an asteroid that
+/// drifts.
+fn parse_asteroid(words: &Words) -> Result<LevelAssetObject, ParseError> {
+    words.expect_len(&[4, 6], "Asteroid x y radius [vx vy]")?;
+
+    let x = words.parse(1, "the x position")?;
+    let y = words.parse(2, "the y position")?;
+    let radius = words.parse(3, "the radius")?;
+    let velocity = if words.len() == 6 {
+        let vx = words.parse(4, "the x velocity")?;
+        let vy = words.parse(5, "the y velocity")?;
+        Some(Vec2::new(vx, vy))
+    } else {
+        None
+    };
+    if radius <= 0.0 {
+        return Err(words.error(3, "a positive radius"));
+    }
+    Ok(LevelAssetObject::Asteroid {
+        position: DVec2::new(x, y),
+        radius,
+        velocity,
+        properties: ObjectProperties::default(),
+    })
+}
+
+/// `Station x y`, or `Station x y vx vy` for a station that moves.
+fn parse_station(words: &Words) -> Result<LevelAssetObject, ParseError> {
+    words.expect_len(&[3, 5], "Station x y [vx vy]")?;
+
+    let x = words.parse(1, "the x position")?;
+    let y = words.parse(2, "the y position")?;
+    let velocity = if words.len() == 5 {
+        let vx = words.parse(3, "the x velocity")?;
+        let vy = words.parse(4, "the y velocity")?;
+        Some(Vec2::new(vx, vy))
+    } else {
+        None
+    };
+    Ok(LevelAssetObject::Station {
+        position: DVec2::new(x, y),
+        velocity,
+    })
+}
+
+/// `FuelDepot x y radius [rate]`, where `rate` is fuel per second.
+fn parse_fuel_depot(words: &Words) -> Result<LevelAssetObject, ParseError> {
+    words.expect_len(&[4, 5], "FuelDepot x y radius [rate]")?;
+
+    let x = words.parse(1, "the x position")?;
+    let y = words.parse(2, "the y position")?;
+    let radius = words.parse(3, "the radius")?;
+    let rate = if words.len() == 5 {
+        words.parse(4, "the fuel per second")?
+    } else {
+        DEFAULT_REFUEL_RATE
+    };
+    if radius <= 0.0 {
+        return Err(words.error(3, "a positive radius"));
+    }
+    if rate < 0.0 {
+        return Err(words.error(4, "a rate that isn't negative"));
+    }
+    Ok(LevelAssetObject::FuelDepot {
+        position: DVec2::new(x, y),
+        radius,
+        rate,
+    })
+}
+
+/// `Goal x y radius`.
+fn parse_goal(words: &Words) -> Result<LevelAssetObject, ParseError> {
+    words.expect_len(&[4], "Goal x y radius")?;
+
+    let x = words.parse(1, "the x position")?;
+    let y = words.parse(2, "the y position")?;
+    let radius = words.parse(3, "the radius")?;
+    if radius <= 0.0 {
+        return Err(words.error(3, "a positive radius"));
+    }
+    Ok(LevelAssetObject::Goal {
+        position: DVec2::new(x, y),
+        radius,
+    })
+}
+
+/// `Ship x y`, or `Ship x y vx vy rotation [fuel]` for a ship that starts
+/// moving, where `rotation` is in degrees. There can only be one.
+fn parse_ship(words: &Words, objects: &[LevelAssetObject]) -> Result<LevelAssetObject, ParseError> {
+    words.expect_len(&[3, 6, 7], "Ship x y [vx vy rotation [fuel]]")?;
+    if objects
+        .iter()
+        .any(|object| matches!(object, LevelAssetObject::ShipSpawn { .. }))
+    {
+        return Err(words.error(0, "only one `Ship` line"));
+    }
+
+    let x = words.parse(1, "the x position")?;
+    let y = words.parse(2, "the y position")?;
+    let (velocity, rotation) = if words.len() >= 6 {
+        let vx = words.parse(3, "the x velocity")?;
+        let vy = words.parse(4, "the y velocity")?;
+        (Vec2::new(vx, vy), words.parse(5, "the rotation, in degrees")?)
+    } else {
+        (Vec2::ZERO, 0.0)
+    };
+    let fuel = if words.len() == 7 {
+        Some(words.parse(6, "the fuel")?)
+    } else {
+        None
+    };
+    Ok(LevelAssetObject::ShipSpawn {
+        position: DVec2::new(x, y),
+        velocity,
+        rotation,
+        fuel,
+    })
+}
+
+/// `Atmosphere height density scale-height sky-color`, where `sky-color` is
+/// hex. Gives the object before it an atmosphere.
+fn parse_atmosphere(words: &Words) -> Result<Atmosphere, ParseError> {
+    words.expect_len(&[5], "Atmosphere height density scale-height sky-color")?;
+
+    let height: f32 = words.parse(1, "the height")?;
+    let density = words.parse(2, "the density")?;
+    let scale_height: f32 = words.parse(3, "the scale height")?;
+    let sky_color = words
+        .get(4)
+        .and_then(|word| Color::hex(word).ok())
+        .ok_or_else(|| words.error(4, "a hex sky color"))?;
+    if height <= 0.0 {
+        return Err(words.error(1, "a positive height"));
+    }
+    if scale_height <= 0.0 {
+        return Err(words.error(3, "a positive scale height"));
+    }
+    Ok(Atmosphere {
+        height,
+        density,
+        scale_height,
+        sky_color,
+    })
+}
+
+/// `Hull polygon x1 y1 x2 y2 ...` with at least 3 counter-clockwise convex
+/// vertices, or `Hull capsule half-length radius`. Gives the object before it
+/// a non-circular shape to collide with.
+fn parse_hull(words: &Words) -> Result<Shape, ParseError> {
+    match words.get(1) {
+        Some("polygon") => {
+            let coordinates = (2..words.len())
+                .map(|index| words.parse::<f32>(index, "a vertex coordinate"))
+                .collect::<Result<Vec<_>, _>>()?;
+            if coordinates.len() < 6 || coordinates.len() % 2 != 0 {
+                let expected = "pairs of coordinates for at least 3 vertices";
+                return Err(words.error(words.len(), expected));
+            }
+            let vertices = coordinates
+                .chunks(2)
+                .map(|pair| Vec2::new(pair[0], pair[1]))
+                .collect::<Vec<_>>();
+            if !is_convex_polygon(&vertices) {
+                let expected = "a convex polygon, with counter-clockwise vertices";
+                return Err(words.error(2, expected));
+            }
+            Ok(Shape::Polygon(vertices))
+        }
+        Some("capsule") => {
+            words.expect_len(&[4], "Hull capsule half-length radius")?;
+            let half_length: f32 = words.parse(2, "the half length")?;
+            let radius: f32 = words.parse(3, "the radius")?;
+            if half_length < 0.0 {
+                return Err(words.error(2, "a half length that isn't negative"));
+            }
+            if radius <= 0.0 {
+                return Err(words.error(3, "a positive radius"));
+            }
+            Ok(Shape::Capsule {
+                half_length,
+                radius,
+            })
+        }
+        _ => Err(words.error(1, "`polygon` or `capsule`")),
+    }
+}
+
+/// `Density density`. Gives the object before it it's own mass per cubed
+/// unit of radius.
+fn parse_density(words: &Words) -> Result<f32, ParseError> {
+    words.expect_len(&[2], "Density density")?;
+
+    let density = words.parse(1, "the density")?;
+    if density < 0.0 {
+        return Err(words.error(1, "a density that isn't negative"));
+    }
+    Ok(density)
+}
+
+/// `Gravity constant|softening|density|falloff value`. Sets one field of the
+/// level's `GravityConfig`.
+fn parse_gravity(words: &Words) -> Result<Line, ParseError> {
+    words.expect_len(&[3], "Gravity constant|softening|density|falloff value")?;
+
+    let parameter = match words.get(1).unwrap_or_default() {
+        "constant" => GravityParameter::Constant,
+        "softening" => GravityParameter::Softening,
+        "density" => GravityParameter::Density,
+        "falloff" => GravityParameter::Falloff,
+        _ => {
+            let expected = "one of `constant`, `softening`, `density` or `falloff`";
+            return Err(words.error(1, expected));
+        }
+    };
+    let value = words.parse(2, "the value")?;
+    if !is_valid_gravity_value(value) {
+        return Err(words.error(2, "a value that isn't negative"));
+    }
+    Ok(Line::Gravity(parameter, value))
+}
+
+/// Can a field of `GravityConfig` be set to this? None of them can be negative.
+pub(super) fn is_valid_gravity_value(value: f32) -> bool {
+    value >= 0.0
+}
+
+/// Are the vertices a convex polygon, in counter-clockwise order?
+pub(super) fn is_convex_polygon(vertices: &[Vec2]) -> bool {
+    vertices.len() >= 3
+        && (0..vertices.len()).all(|i| {
+            let a = vertices[i];
+            let b = vertices[(i + 1) % vertices.len()];
+            let c = vertices[(i + 2) % vertices.len()];
+            (b - a).perp_dot(c - b) > 0.0
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> ParseError {
+        parse_level(source).expect_err("the level should be rejected")
+    }
+
+    fn error_at(line: usize, column: usize, found: &str, expected: &str) -> ParseError {
+        ParseError {
+            line,
+            column,
+            found: found.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_the_bundled_levels() {
+        let level = parse_level(include_str!("../../assets/Level1.level.txt")).unwrap();
+        assert_eq!(level.objects.len(), 4);
+        let LevelAssetObject::Planet { properties, .. } = &level.objects[1] else {
+            panic!("the second object should be a planet");
+        };
+        assert!(matches!(properties.hull, Some(Shape::Polygon(_))));
+        assert!(matches!(level.objects[2], LevelAssetObject::Moon { parent: 0, .. }));
+
+        parse_level(include_str!("../../assets/Level2.level.txt")).unwrap();
+    }
+
+    #[test]
+    fn skips_blank_and_comment_lines() {
+        let level = parse_level(
+            "\n# A comment\n  \t \nPlanet 1 2 3 # on the same line\n\tShip  4\t5\n#Planet 0 0 1",
+        )
+        .unwrap();
+        assert_eq!(
+            level.objects,
+            vec![
+                LevelAssetObject::Planet {
+                    position: DVec2::new(1.0, 2.0),
+                    radius: 3.0,
+                    velocity: None,
+                    properties: ObjectProperties::default(),
+                },
+                LevelAssetObject::ShipSpawn {
+                    position: DVec2::new(4.0, 5.0),
+                    velocity: Vec2::ZERO,
+                    rotation: 0.0,
+                    fuel: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_columns_in_characters_across_any_whitespace() {
+        assert_eq!(
+            error("\t Planet  x 0 10"),
+            error_at(1, 11, "x", "the x position")
+        );
+        // A non-breaking space is two bytes, but one column.
+        assert_eq!(
+            error("Planet\u{a0}1 y 10"),
+            error_at(1, 10, "y", "the y position")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keywords() {
+        assert_eq!(
+            error("Ship 0 0\n\nPlanit 0 0 10"),
+            error_at(
+                3,
+                1,
+                "Planit",
+                "an object like `Planet` or `Ship`, or a modifier like `Hull` or `Gravity`",
+            )
+        );
+        assert_eq!(
+            error("Gravity mass 1"),
+            error_at(
+                1,
+                9,
+                "mass",
+                "one of `constant`, `softening`, `density` or `falloff`",
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_the_wrong_number_of_words() {
+        assert_eq!(
+            error("Planet 0 0"),
+            error_at(1, 11, "", "more words, like `Planet x y radius [vx vy]`")
+        );
+        // Between the two lengths a planet can have.
+        assert_eq!(
+            error("Planet 0 0 10 1"),
+            error_at(1, 16, "", "more words, like `Planet x y radius [vx vy]`")
+        );
+        assert_eq!(
+            error("Goal 0 0 10 20"),
+            error_at(1, 13, "20", "the end of the line, like `Goal x y radius`")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        assert_eq!(error("Planet 0 0 big"), error_at(1, 12, "big", "the radius"));
+        assert_eq!(
+            error("Planet 0 0 10\nMoon 0 10 1e 0 0"),
+            error_at(2, 11, "1e", "the semi-major axis")
+        );
+        assert_eq!(
+            error("Gravity softening -1"),
+            error_at(1, 19, "-1", "a value that isn't negative")
+        );
+        assert_eq!(
+            error("Planet 0 0 10\nDensity -2"),
+            error_at(2, 9, "-2", "a density that isn't negative")
+        );
+    }
+
+    #[test]
+    fn rejects_radii_that_arent_positive() {
+        let expected = "a positive radius";
+        assert_eq!(error("Planet 0 0 -10"), error_at(1, 12, "-10", expected));
+        assert_eq!(error("Asteroid 0 0 0 1 1"), error_at(1, 14, "0", expected));
+        assert_eq!(error("FuelDepot 0 0 -1 5"), error_at(1, 15, "-1", expected));
+        assert_eq!(error("Goal 0 0 0"), error_at(1, 10, "0", expected));
+    }
+
+    #[test]
+    fn rejects_bad_moons() {
+        assert_eq!(
+            error("Planet 0 0 10\nMoon 0 -5 100 0 0"),
+            error_at(2, 8, "-5", "a positive radius")
+        );
+        assert_eq!(
+            error("Planet 0 0 10\nMoon 0 5 0 0 0"),
+            error_at(2, 10, "0", "a positive semi-major axis")
+        );
+        assert_eq!(
+            error("Planet 0 0 10\nMoon 0 5 100 1 0"),
+            error_at(2, 14, "1", "an eccentricity in [0, 1)")
+        );
+    }
+
+    #[test]
+    fn rejects_a_moon_whose_parent_comes_after_it() {
+        assert_eq!(
+            error("Moon 1 10 100 0 0\nPlanet 0 0 50"),
+            error_at(1, 6, "1", "the index of a planet or moon before this one")
+        );
+        // Or that isn't a body at all.
+        assert_eq!(
+            error("Goal 0 0 10\nMoon 0 10 100 0 0"),
+            error_at(2, 6, "0", "the index of a planet or moon before this one")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_atmospheres() {
+        assert_eq!(
+            error("Planet 0 0 10\nAtmosphere 0 0.002 120 88b4db"),
+            error_at(2, 12, "0", "a positive height")
+        );
+        assert_eq!(
+            error("Planet 0 0 10\nAtmosphere 400 0.002 -1 88b4db"),
+            error_at(2, 22, "-1", "a positive scale height")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_hulls() {
+        assert_eq!(
+            error("Planet 0 0 10\nHull polygon 0 0 1 0 1"),
+            error_at(2, 23, "", "pairs of coordinates for at least 3 vertices")
+        );
+        assert_eq!(
+            error("Planet 0 0 10\nHull polygon 0 0 0 1 1 0"),
+            error_at(2, 14, "0", "a convex polygon, with counter-clockwise vertices")
+        );
+        assert_eq!(
+            error("Planet 0 0 10\nHull capsule -1 1"),
+            error_at(2, 14, "-1", "a half length that isn't negative")
+        );
+        assert_eq!(
+            error("Planet 0 0 10\nHull capsule 0 -1"),
+            error_at(2, 16, "-1", "a positive radius")
+        );
+        assert_eq!(
+            error("Hull capsule 1 1"),
+            error_at(1, 1, "Hull", "a planet, moon or asteroid before this line")
+        );
+    }
+
+    #[test]
+    fn rejects_a_second_ship() {
+        assert_eq!(
+            error("Ship 0 0\nShip 1 1"),
+            error_at(2, 1, "Ship", "only one `Ship` line")
+        );
+    }
+
+    #[test]
+    fn needs_a_ship() {
+        assert_eq!(
+            error("Planet 0 0 10\n\n"),
+            error_at(3, 1, "", "a `Ship` line somewhere in the level")
+        );
+    }
+
+    #[test]
+    fn displays_where_the_error_is() {
+        assert_eq!(
+            error("Planet 0 0 big").to_string(),
+            "line 1, column 12: expected the radius, found \"big\""
+        );
+        assert_eq!(
+            error("Planet 0 0").to_string(),
+            "line 1, column 11: expected more words, like `Planet x y radius [vx vy]`, \
+             found the end of the line"
+        );
+    }
+}
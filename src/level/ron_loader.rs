@@ -0,0 +1,615 @@
+use bevy::{
+    asset::{AssetLoader, Error, LoadedAsset},
+    prelude::*,
+    utils::HashMap,
+};
+use ron::extensions::Extensions;
+use serde::Deserialize;
+
+use super::{
+    loader::{is_convex_polygon, is_valid_gravity_value},
+    LevelAsset, LevelAssetObject, ObjectProperties,
+};
+use crate::{
+    fuel_depot::DEFAULT_REFUEL_RATE,
+    physics::{Atmosphere, GravityConfig, Shape},
+};
+
+/// Loads `.level.ron` files. Unlike the text format, objects have names, moons
+/// refer to their parent by name, and everything optional can be left out:
+///
+/// ```ron
+/// (
+///     gravity: (softening: 50),
+///     ship: (position: (5000, 1100), velocity: (-40, 0), rotation: 180),
+///     objects: [
+///         (
+///             name: "home",
+///             kind: Planet(position: (5000, 0), radius: 1000),
+///             atmosphere: (height: 400, density: 0.002, scale_height: 120, sky_color: "88b4db"),
+///         ),
+///         (name: "moon", kind: Moon(parent: "home", radius: 150, semi_major_axis: 4000)),
+///     ],
+/// )
+/// ```
+pub struct RonLevelAssetLoader;
+
+impl AssetLoader for RonLevelAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let level = parse_ron_level(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+fn parse_ron_level(bytes: &[u8]) -> Result<LevelAsset, Error> {
+    let options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+    let file = options.from_bytes::<LevelFile>(bytes)?;
+    file.into_level()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    #[serde(default)]
+    gravity: GravityFile,
+    ship: ShipFile,
+    objects: Vec<ObjectFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShipFile {
+    position: (f64, f64),
+    #[serde(default)]
+    velocity: (f32, f32),
+    /// In degrees.
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default)]
+    fuel: Option<f32>,
+}
+
+/// `GravityConfig`, where every field defaults to the usual gravity.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GravityFile {
+    constant: f32,
+    softening: f32,
+    density: f32,
+    falloff: f32,
+}
+
+impl Default for GravityFile {
+    fn default() -> Self {
+        let config = GravityConfig::default();
+        Self {
+            constant: config.gravitational_constant,
+            softening: config.softening,
+            density: config.density,
+            falloff: config.falloff,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectFile {
+    /// Only needed to be the parent of a moon.
+    #[serde(default)]
+    name: Option<String>,
+    kind: ObjectKind,
+    #[serde(default)]
+    atmosphere: Option<AtmosphereFile>,
+    #[serde(default)]
+    hull: Option<HullFile>,
+    #[serde(default)]
+    density: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum ObjectKind {
+    Planet {
+        position: (f64, f64),
+        radius: f32,
+        #[serde(default)]
+        velocity: Option<(f32, f32)>,
+    },
+    Moon {
+        parent: String,
+        radius: f32,
+        semi_major_axis: f32,
+        #[serde(default)]
+        eccentricity: f32,
+        /// In degrees.
+        #[serde(default)]
+        phase: f32,
+    },
+    Asteroid {
+        position: (f64, f64),
+        radius: f32,
+        #[serde(default)]
+        velocity: Option<(f32, f32)>,
+    },
+    Station {
+        position: (f64, f64),
+        #[serde(default)]
+        velocity: Option<(f32, f32)>,
+    },
+    FuelDepot {
+        position: (f64, f64),
+        radius: f32,
+        #[serde(default = "default_refuel_rate")]
+        rate: f32,
+    },
+    Goal {
+        position: (f64, f64),
+        radius: f32,
+    },
+}
+
+fn default_refuel_rate() -> f32 {
+    DEFAULT_REFUEL_RATE
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AtmosphereFile {
+    height: f32,
+    density: f32,
+    scale_height: f32,
+    /// Hex, like in the text format.
+    sky_color: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum HullFile {
+    /// At least 3 counter-clockwise convex vertices.
+    Polygon(Vec<(f32, f32)>),
+    Capsule { half_length: f32, radius: f32 },
+}
+
+impl LevelFile {
+    fn into_level(self) -> Result<LevelAsset, Error> {
+        let fields = [
+            ("constant", self.gravity.constant),
+            ("softening", self.gravity.softening),
+            ("density", self.gravity.density),
+            ("falloff", self.gravity.falloff),
+        ];
+        for (field, value) in fields {
+            if !is_valid_gravity_value(value) {
+                return Err(Error::msg(format!("The gravity's {field} can't be negative")));
+            }
+        }
+        let gravity = GravityConfig {
+            gravitational_constant: self.gravity.constant,
+            softening: self.gravity.softening,
+            density: self.gravity.density,
+            falloff: self.gravity.falloff,
+        };
+
+        // The index of every named object, for moons to find their parent.
+        let mut indices = HashMap::new();
+        let mut objects = Vec::new();
+        for object in self.objects {
+            if let Some(name) = &object.name {
+                if indices.insert(name.clone(), objects.len()).is_some() {
+                    return Err(Error::msg(format!("Two objects are named {name:?}")));
+                }
+            }
+            objects.push(object.into_object(&indices, &objects)?);
+        }
+        objects.push(LevelAssetObject::ShipSpawn {
+            position: self.ship.position.into(),
+            velocity: self.ship.velocity.into(),
+            rotation: self.ship.rotation,
+            fuel: self.ship.fuel,
+        });
+
+        Ok(LevelAsset { objects, gravity })
+    }
+}
+
+impl ObjectFile {
+    /// Converts the object, given the ones before it.
+    fn into_object(
+        self,
+        indices: &HashMap<String, usize>,
+        objects: &[LevelAssetObject],
+    ) -> Result<LevelAssetObject, Error> {
+        let has_properties =
+            self.atmosphere.is_some() || self.hull.is_some() || self.density.is_some();
+        let atmosphere = self.atmosphere.map(AtmosphereFile::into_atmosphere).transpose()?;
+        let hull = self.hull.map(HullFile::into_shape).transpose()?;
+        if self.density.is_some_and(|density| density < 0.0) {
+            return Err(Error::msg("A density can't be negative"));
+        }
+        let properties = ObjectProperties {
+            name: self.name,
+            atmosphere,
+            hull,
+            density: self.density,
+        };
+
+        let mut object = match self.kind {
+            ObjectKind::Planet {
+                position,
+                radius,
+                velocity,
+            } => {
+                if radius <= 0.0 {
+                    return Err(Error::msg("A planet's radius must be positive"));
+                }
+                LevelAssetObject::Planet {
+                    position: position.into(),
+                    radius,
+                    velocity: velocity.map(Vec2::from),
+                    properties,
+                }
+            }
+            ObjectKind::Moon {
+                parent,
+                radius,
+                semi_major_axis,
+                eccentricity,
+                phase,
+            } => {
+                // Only objects before this one are in `indices` yet.
+                let parent = *indices.get(&parent).ok_or_else(|| {
+                    Error::msg(format!("A moon's parent {parent:?} must come before it"))
+                })?;
+                if !objects[parent].is_body() {
+                    return Err(Error::msg("A moon's parent must be a planet or moon"));
+                }
+                if radius <= 0.0 {
+                    return Err(Error::msg("A moon's radius must be positive"));
+                }
+                if semi_major_axis <= 0.0 {
+                    return Err(Error::msg("A moon's semi-major axis must be positive"));
+                }
+                if !(0.0..1.0).contains(&eccentricity) {
+                    return Err(Error::msg("A moon's eccentricity must be in [0, 1)"));
+                }
+                LevelAssetObject::Moon {
+                    parent,
+                    radius,
+                    semi_major_axis,
+                    eccentricity,
+                    phase,
+                    properties,
+                }
+            }
+            ObjectKind::Asteroid {
+                position,
+                radius,
+                velocity,
+            } => {
+                if radius <= 0.0 {
+                    return Err(Error::msg("An asteroid's radius must be positive"));
+                }
+                LevelAssetObject::Asteroid {
+                    position: position.into(),
+                    radius,
+                    velocity: velocity.map(Vec2::from),
+                    properties,
+                }
+            }
+            ObjectKind::Station { position, velocity } => LevelAssetObject::Station {
+                position: position.into(),
+                velocity: velocity.map(Vec2::from),
+            },
+            ObjectKind::FuelDepot {
+                position,
+                radius,
+                rate,
+            } => {
+                if radius <= 0.0 {
+                    return Err(Error::msg("A fuel depot's radius must be positive"));
+                }
+                if rate < 0.0 {
+                    return Err(Error::msg("A fuel depot's rate can't be negative"));
+                }
+                LevelAssetObject::FuelDepot {
+                    position: position.into(),
+                    radius,
+                    rate,
+                }
+            }
+            ObjectKind::Goal { position, radius } => {
+                if radius <= 0.0 {
+                    return Err(Error::msg("A goal's radius must be positive"));
+                }
+                LevelAssetObject::Goal {
+                    position: position.into(),
+                    radius,
+                }
+            }
+        };
+
+        if has_properties && object.properties_mut().is_none() {
+            return Err(Error::msg(
+                "Only planets, moons and asteroids can have an atmosphere, hull or density",
+            ));
+        }
+        Ok(object)
+    }
+}
+
+impl AtmosphereFile {
+    fn into_atmosphere(self) -> Result<Atmosphere, Error> {
+        if self.height <= 0.0 {
+            return Err(Error::msg("An atmosphere's height must be positive"));
+        }
+        if self.scale_height <= 0.0 {
+            return Err(Error::msg("An atmosphere's scale height must be positive"));
+        }
+        Ok(Atmosphere {
+            height: self.height,
+            density: self.density,
+            scale_height: self.scale_height,
+            sky_color: Color::hex(&self.sky_color)?,
+        })
+    }
+}
+
+impl HullFile {
+    fn into_shape(self) -> Result<Shape, Error> {
+        match self {
+            HullFile::Polygon(vertices) => {
+                let vertices = vertices.into_iter().map(Vec2::from).collect::<Vec<_>>();
+                if !is_convex_polygon(&vertices) {
+                    return Err(Error::msg(
+                        "A polygon must have at least 3 convex, counter-clockwise vertices",
+                    ));
+                }
+                Ok(Shape::Polygon(vertices))
+            }
+            HullFile::Capsule {
+                half_length,
+                radius,
+            } => {
+                if half_length < 0.0 {
+                    return Err(Error::msg("A capsule's half length can't be negative"));
+                }
+                if radius <= 0.0 {
+                    return Err(Error::msg("A capsule's radius must be positive"));
+                }
+                Ok(Shape::Capsule {
+                    half_length,
+                    radius,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Why the level is rejected. It gets a ship first, so that isn't why.
+    fn error(source: &str) -> String {
+        let source = source.replacen('(', "(ship: (position: (0, 0)), ", 1);
+        parse_ron_level(source.as_bytes())
+            .expect_err("the level should be rejected")
+            .to_string()
+    }
+
+    #[test]
+    fn parses_the_bundled_level() {
+        let level = parse_ron_level(include_bytes!("../../assets/Level1.level.ron")).unwrap();
+        assert_eq!(level.objects.len(), 4);
+        assert!(matches!(
+            level.objects[2],
+            LevelAssetObject::Moon { parent: 0, .. }
+        ));
+        assert!(matches!(
+            level.objects[3],
+            LevelAssetObject::ShipSpawn { .. }
+        ));
+    }
+
+    #[test]
+    fn leaves_out_optional_fields() {
+        let level = parse_ron_level(
+            br#"(
+                gravity: (softening: 50),
+                ship: (position: (1, 2)),
+                objects: [(kind: Asteroid(position: (3, 4), radius: 5))],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(level.gravity.softening, 50.0);
+        assert_eq!(
+            level.gravity.gravitational_constant,
+            GravityConfig::default().gravitational_constant
+        );
+        assert_eq!(
+            level.objects[0],
+            LevelAssetObject::Asteroid {
+                position: (3.0, 4.0).into(),
+                radius: 5.0,
+                velocity: None,
+                properties: ObjectProperties::default(),
+            }
+        );
+        assert_eq!(
+            level.objects[1],
+            LevelAssetObject::ShipSpawn {
+                position: (1.0, 2.0).into(),
+                velocity: Vec2::ZERO,
+                rotation: 0.0,
+                fuel: None,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_negative_gravity() {
+        for field in ["constant", "softening", "density", "falloff"] {
+            let source = format!("(gravity: ({field}: -1), objects: [])");
+            assert_eq!(
+                error(&source),
+                format!("The gravity's {field} can't be negative")
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_radii_that_arent_positive() {
+        assert_eq!(
+            error("(objects: [(kind: Planet(position: (0, 0), radius: -10))])"),
+            "A planet's radius must be positive"
+        );
+        assert_eq!(
+            error("(objects: [(kind: Asteroid(position: (0, 0), radius: 0))])"),
+            "An asteroid's radius must be positive"
+        );
+        assert_eq!(
+            error("(objects: [(kind: FuelDepot(position: (0, 0), radius: -1))])"),
+            "A fuel depot's radius must be positive"
+        );
+        assert_eq!(
+            error("(objects: [(kind: Goal(position: (0, 0), radius: 0))])"),
+            "A goal's radius must be positive"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_moons() {
+        let moon = |fields: &str| {
+            format!(
+                r#"(objects: [
+                    (name: "home", kind: Planet(position: (0, 0), radius: 100)),
+                    (kind: Moon(parent: "home", {fields})),
+                ])"#
+            )
+        };
+        assert_eq!(
+            error(&moon("radius: 0, semi_major_axis: 500")),
+            "A moon's radius must be positive"
+        );
+        assert_eq!(
+            error(&moon("radius: 10, semi_major_axis: -500")),
+            "A moon's semi-major axis must be positive"
+        );
+        assert_eq!(
+            error(&moon("radius: 10, semi_major_axis: 500, eccentricity: 1")),
+            "A moon's eccentricity must be in [0, 1)"
+        );
+        assert_eq!(
+            error(
+                r#"(objects: [
+                    (kind: Moon(parent: "home", radius: 10, semi_major_axis: 500)),
+                    (name: "home", kind: Planet(position: (0, 0), radius: 100)),
+                ])"#
+            ),
+            r#"A moon's parent "home" must come before it"#
+        );
+        assert_eq!(
+            error(
+                r#"(objects: [
+                    (name: "goal", kind: Goal(position: (0, 0), radius: 100)),
+                    (kind: Moon(parent: "goal", radius: 10, semi_major_axis: 500)),
+                ])"#
+            ),
+            "A moon's parent must be a planet or moon"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_objects() {
+        assert_eq!(
+            error(
+                r#"(objects: [
+                    (name: "a", kind: Goal(position: (0, 0), radius: 1)),
+                    (name: "a", kind: Goal(position: (0, 0), radius: 1)),
+                ])"#
+            ),
+            r#"Two objects are named "a""#
+        );
+        assert_eq!(
+            error("(objects: [(kind: Station(position: (0, 0)), density: 1)])"),
+            "Only planets, moons and asteroids can have an atmosphere, hull or density"
+        );
+        assert_eq!(
+            error("(objects: [(kind: Asteroid(position: (0, 0), radius: 1), density: -1)])"),
+            "A density can't be negative"
+        );
+        assert_eq!(
+            error(
+                "(objects: [(
+                    kind: Asteroid(position: (0, 0), radius: 1),
+                    hull: Polygon([(0, 0), (0, 1), (1, 0)]),
+                )])"
+            ),
+            "A polygon must have at least 3 convex, counter-clockwise vertices"
+        );
+        assert_eq!(
+            error(
+                "(objects: [(
+                    kind: Asteroid(position: (0, 0), radius: 1),
+                    hull: Capsule(half_length: -1, radius: 1),
+                )])"
+            ),
+            "A capsule's half length can't be negative"
+        );
+        assert_eq!(
+            error(
+                "(objects: [(
+                    kind: Asteroid(position: (0, 0), radius: 1),
+                    hull: Capsule(half_length: 1, radius: 0),
+                )])"
+            ),
+            "A capsule's radius must be positive"
+        );
+        assert_eq!(
+            error("(objects: [(kind: FuelDepot(position: (0, 0), radius: 1, rate: -1))])"),
+            "A fuel depot's rate can't be negative"
+        );
+    }
+
+    #[test]
+    fn needs_a_ship() {
+        let error = parse_ron_level(b"(objects: [])").unwrap_err().to_string();
+        assert!(error.contains("missing field `ship`"), "{error}");
+    }
+
+    #[test]
+    fn rejects_bad_atmospheres() {
+        let atmosphere = |fields: &str| {
+            format!(
+                r#"(objects: [(
+                    kind: Planet(position: (0, 0), radius: 100),
+                    atmosphere: ({fields}, sky_color: "88b4db"),
+                )])"#
+            )
+        };
+        assert_eq!(
+            error(&atmosphere("height: 0, density: 0.002, scale_height: 120")),
+            "An atmosphere's height must be positive"
+        );
+        assert_eq!(
+            error(&atmosphere("height: 400, density: 0.002, scale_height: -1")),
+            "An atmosphere's scale height must be positive"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(parse_ron_level(b"(objects: [], spaceship: (position: (0, 0)))").is_err());
+    }
+}
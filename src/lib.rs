@@ -1,11 +1,22 @@
+#![allow(clippy::type_complexity)]
+
+mod apsis_markers;
+mod asteroid;
+mod autopilot;
 mod camera;
+mod contact;
 mod player;
+mod floating_origin;
+mod fuel_depot;
 mod fuelbar;
+mod goal;
 mod level;
+mod maneuver;
 mod physics;
 mod physics_prediction;
 mod planet;
 mod ship;
+mod station;
 mod time;
 
 use bevy::{app::AppExit, prelude::*};
@@ -22,24 +33,61 @@ pub fn main() {
             camera::CameraPlugin,
             ship::ShipPlugin,
             physics::PhysicsPlugin,
-            planet::PlanetPlugin,
+            (
+                planet::PlanetPlugin,
+                station::StationPlugin,
+                fuel_depot::FuelDepotPlugin,
+                goal::GoalPlugin,
+            ),
             physics_prediction::PhysicsPredictionPlugin,
             fuelbar::FuelBarPlugin,
             level::LevelPlugin,
             time::TimePlugin,
             player::PlayerPlugin,
+            apsis_markers::ApsisMarkersPlugin,
+            maneuver::ManeuverPlugin,
+            autopilot::AutopilotPlugin,
+            floating_origin::FloatingOriginPlugin,
+            contact::ContactPlugin,
         ))
         .insert_resource(ClearColor(Color::hex("1d2b53").unwrap()))
         .add_systems(Startup, setup)
-        .add_systems(Update, (stop_on_escape_system, set_time_scale))
+        .add_systems(
+            Update,
+            (
+                stop_on_escape_system,
+                set_time_scale,
+                cycle_integrator,
+                cycle_gravity_mode,
+                switch_level,
+            ),
+        )
         .run();
 }
 
-fn setup(asset_server: Res<AssetServer>, mut commands: Commands) {
-    commands.spawn(level::Level {
-        level_asset: asset_server.load("Level1.txt"),
-        ..default()
-    });
+/// The levels, in the order they are played.
+const LEVELS: [&str; 3] = ["Level1.level.txt", "Level2.level.txt", "Level1.level.ron"];
+
+fn setup(mut level_events: EventWriter<level::LevelEvent>) {
+    level_events.send(level::LevelEvent::Load(LEVELS[0].to_string()));
+}
+
+/// F5 restarts the level, N goes to the next one, and F4 unloads it.
+fn switch_level(
+    input: Res<Input<KeyCode>>,
+    mut current: Local<usize>,
+    mut level_events: EventWriter<level::LevelEvent>,
+) {
+    if input.just_pressed(KeyCode::F4) {
+        level_events.send(level::LevelEvent::Unload);
+        return;
+    }
+    if input.just_pressed(KeyCode::N) {
+        *current = (*current + 1) % LEVELS.len();
+    } else if !input.just_pressed(KeyCode::F5) {
+        return;
+    }
+    level_events.send(level::LevelEvent::Load(LEVELS[*current].to_string()));
 }
 
 pub fn get_input_dir(input: &Input<KeyCode>) -> Vec2 {
@@ -71,6 +119,20 @@ fn set_time_scale(input: Res<Input<KeyCode>>, mut time_scale: ResMut<time::TimeS
     }
 }
 
+fn cycle_integrator(input: Res<Input<KeyCode>>, mut integrator: ResMut<physics::Integrator>) {
+    if input.just_pressed(KeyCode::I) {
+        *integrator = integrator.next();
+        info!("Integrator: {:?}", *integrator);
+    }
+}
+
+fn cycle_gravity_mode(input: Res<Input<KeyCode>>, mut mode: ResMut<physics::GravityMode>) {
+    if input.just_pressed(KeyCode::G) {
+        *mode = mode.next();
+        info!("Gravity mode: {:?}", *mode);
+    }
+}
+
 /// Zero is returned as 10
 fn get_min_number_pressed(input: &Input<KeyCode>) -> Option<u16> {
     const NUMBER_CODES: [(KeyCode, KeyCode); 10] = [
@@ -0,0 +1,389 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    level::LevelUnloadedEvent,
+    physics::Circle,
+    physics_prediction::{
+        update_predictions, PathPoint, PhysicsPrediction, Prediction, PredictionContext,
+    },
+    ship::{fuel_for_delta_v, Ship},
+    time::TimeScale,
+};
+
+pub struct ManeuverPlugin;
+
+impl Plugin for ManeuverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlannedManeuver>()
+            .init_resource::<ManeuverDrag>()
+            .add_systems(Startup, setup_maneuver)
+            .add_systems(FixedUpdate, maneuver_countdown_system)
+            .add_systems(Update, clear_maneuver_on_unload.before(maneuver_input_system))
+            .add_systems(
+                Update,
+                (
+                    maneuver_input_system,
+                    maneuver_prediction_system
+                        .after(maneuver_input_system)
+                        .before(update_predictions),
+                    maneuver_display_system.after(maneuver_input_system),
+                    maneuver_ui_system.after(maneuver_input_system),
+                ),
+            );
+    }
+}
+
+/// A burn planned at a point on the ship's predicted path. It stays planned
+/// until the autopilot has done the burn, or the player deletes it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ManeuverNode {
+    /// Seconds from now, in simulation time. Negative once the burn is
+    /// overdue.
+    pub time: f32,
+    /// The change in velocity, where `x` is prograde and `y` is normal (to the
+    /// left of prograde).
+    pub delta_v: Vec2,
+}
+
+impl ManeuverNode {
+    /// The prograde and normal directions for a ship moving with `velocity`.
+    pub fn frame(velocity: Vec2) -> (Vec2, Vec2) {
+        let prograde = velocity.try_normalize().unwrap_or(Vec2::X);
+        (prograde, prograde.perp())
+    }
+
+    /// The change in velocity, in world space.
+    pub fn world_delta_v(&self, velocity: Vec2) -> Vec2 {
+        let (prograde, normal) = Self::frame(velocity);
+        prograde * self.delta_v.x + normal * self.delta_v.y
+    }
+
+    /// Where on the predicted path the burn is. An overdue burn is where the
+    /// ship is now.
+    pub fn point(&self, prediction: &Prediction) -> Option<PathPoint> {
+        prediction.sample(self.time.max(0.0))
+    }
+
+    pub fn fuel_cost(&self) -> f32 {
+        fuel_for_delta_v(self.delta_v.length())
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct PlannedManeuver(pub Option<ManeuverNode>);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum ManeuverHandle {
+    Prograde,
+    Normal,
+}
+
+impl ManeuverHandle {
+    fn axis(self, velocity: Vec2) -> Vec2 {
+        let (prograde, normal) = ManeuverNode::frame(velocity);
+        match self {
+            ManeuverHandle::Prograde => prograde,
+            ManeuverHandle::Normal => normal,
+        }
+    }
+
+    fn component(self, delta_v: &mut Vec2) -> &mut f32 {
+        match self {
+            ManeuverHandle::Prograde => &mut delta_v.x,
+            ManeuverHandle::Normal => &mut delta_v.y,
+        }
+    }
+}
+
+/// A handle being dragged, and where the drag started.
+#[derive(Resource, Debug, Default)]
+struct ManeuverDrag(Option<(ManeuverHandle, Vec2, f32)>);
+
+#[derive(Component)]
+struct ManeuverNodeMarker;
+
+/// The predicted path after the burn.
+#[derive(Component)]
+struct ManeuverPrediction;
+
+#[derive(Component)]
+struct ManeuverText;
+
+const NODE_RADIUS: f32 = 5.0;
+const HANDLE_RADIUS: f32 = 4.0;
+/// How far the handles are from the node, in pixels.
+const HANDLE_DISTANCE: f32 = 30.0;
+/// How close the cursor has to be to something to grab it, in pixels.
+const GRAB_DISTANCE: f32 = 10.0;
+const DELTA_V_PER_PIXEL: f32 = 0.5;
+
+fn setup_maneuver(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn((
+        ManeuverPrediction,
+        Prediction::default(),
+        ColorMesh2dBundle {
+            mesh: meshes.add(Mesh::from(shape::Circle::new(1.0))).into(),
+            material: materials.add(Color::rgb(0.2, 0.6, 0.3).into()),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        ManeuverNodeMarker,
+        ColorMesh2dBundle {
+            mesh: meshes
+                .add(Mesh::from(shape::Circle::new(NODE_RADIUS)))
+                .into(),
+            material: materials.add(Color::rgb(0.3, 0.5, 0.9).into()),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+
+    let handle_mesh = meshes.add(Mesh::from(shape::Circle::new(HANDLE_RADIUS)));
+    for (handle, color) in [
+        (ManeuverHandle::Prograde, Color::rgb(0.9, 0.8, 0.2)),
+        (ManeuverHandle::Normal, Color::rgb(0.8, 0.3, 0.8)),
+    ] {
+        commands.spawn((
+            handle,
+            ColorMesh2dBundle {
+                mesh: handle_mesh.clone().into(),
+                material: materials.add(color.into()),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ));
+    }
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("pixeboy.ttf"),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_alignment(TextAlignment::Right)
+        .with_no_wrap()
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(30.),
+            right: Val::Px(5.),
+            ..default()
+        }),
+        ManeuverText,
+    ));
+}
+
+fn maneuver_countdown_system(
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+    mut maneuver: ResMut<PlannedManeuver>,
+) {
+    if let Some(node) = &mut maneuver.0 {
+        node.time -= time_scale.delta_f32(&time);
+    }
+}
+
+fn maneuver_input_system(
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform, &OrthographicProjection)>,
+    ship_prediction: Query<&Prediction, With<PhysicsPrediction>>,
+    mut maneuver: ResMut<PlannedManeuver>,
+    mut drag: ResMut<ManeuverDrag>,
+) {
+    if keyboard.just_pressed(KeyCode::Delete) {
+        maneuver.0 = None;
+    }
+    if mouse.just_released(MouseButton::Left) {
+        drag.0 = None;
+    }
+
+    let (camera, camera_transform, projection) = camera.single();
+    let Some(cursor) = window
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let scale = projection.scale;
+    let prediction = ship_prediction.single();
+    let node_point = maneuver.0.and_then(|node| node.point(prediction));
+    if node_point.is_none() {
+        // The node is gone, or the path doesn't reach it anymore.
+        drag.0 = None;
+    }
+
+    // Keep dragging a handle.
+    if let (Some((handle, start_cursor, start_value)), Some(node), Some(point)) =
+        (drag.0, &mut maneuver.0, node_point)
+    {
+        let pixels = (cursor - start_cursor).dot(handle.axis(point.velocity)) / scale;
+        *handle.component(&mut node.delta_v) = start_value + pixels * DELTA_V_PER_PIXEL;
+        return;
+    }
+
+    if mouse.just_pressed(MouseButton::Right) {
+        if let Some(point) = node_point {
+            if point.position.distance(cursor) <= GRAB_DISTANCE * scale {
+                maneuver.0 = None;
+            }
+        }
+    }
+
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // Grab a handle...
+    if let (Some(node), Some(point)) = (&mut maneuver.0, node_point) {
+        for handle in [ManeuverHandle::Prograde, ManeuverHandle::Normal] {
+            let handle_position =
+                point.position + handle.axis(point.velocity) * HANDLE_DISTANCE * scale;
+            if handle_position.distance(cursor) <= GRAB_DISTANCE * scale {
+                drag.0 = Some((handle, cursor, *handle.component(&mut node.delta_v)));
+                return;
+            }
+        }
+    }
+
+    // ...or put the node where the path was clicked.
+    let closest = prediction
+        .points()
+        .iter()
+        .min_by(|a, b| {
+            a.position
+                .distance_squared(cursor)
+                .total_cmp(&b.position.distance_squared(cursor))
+        })
+        .filter(|point| point.position.distance(cursor) <= GRAB_DISTANCE * scale);
+    if let Some(closest) = closest {
+        let now = prediction.points()[0].time;
+        let delta_v = maneuver.0.map_or(Vec2::ZERO, |node| node.delta_v);
+        maneuver.0 = Some(ManeuverNode {
+            time: closest.time - now,
+            delta_v,
+        });
+    }
+}
+
+/// A maneuver planned in one level, or being dragged there, means nothing in
+/// the next.
+fn clear_maneuver_on_unload(
+    mut unloaded: EventReader<LevelUnloadedEvent>,
+    mut maneuver: ResMut<PlannedManeuver>,
+    mut drag: ResMut<ManeuverDrag>,
+) {
+    if unloaded.iter().last().is_some() {
+        maneuver.0 = None;
+        drag.0 = None;
+    }
+}
+
+fn maneuver_prediction_system(
+    ship: Query<(Entity, Option<&Circle>), With<Ship>>,
+    ship_prediction: Query<&Prediction, With<PhysicsPrediction>>,
+    mut maneuver_prediction: Query<
+        &mut Prediction,
+        (With<ManeuverPrediction>, Without<PhysicsPrediction>),
+    >,
+    maneuver: Res<PlannedManeuver>,
+    context: PredictionContext,
+) {
+    let Ok((ship, ship_circle)) = ship.get_single() else {
+        // No level is loaded.
+        maneuver_prediction.single_mut().input = None;
+        return;
+    };
+    let node = maneuver.0.and_then(|node| {
+        let point = node.point(ship_prediction.single())?;
+        Some((node, point))
+    });
+
+    maneuver_prediction.single_mut().input = node.map(|(node, point)| {
+        context.input(
+            ship,
+            point.position,
+            point.velocity + node.world_delta_v(point.velocity),
+            ship_circle.map_or(0.0, |c| c.radius),
+        )
+    });
+}
+
+fn maneuver_display_system(
+    maneuver: Res<PlannedManeuver>,
+    ship_prediction: Query<&Prediction, With<PhysicsPrediction>>,
+    camera: Query<&OrthographicProjection, With<Camera>>,
+    mut node_marker: Query<
+        (&mut Transform, &mut Visibility),
+        (With<ManeuverNodeMarker>, Without<ManeuverHandle>),
+    >,
+    mut handles: Query<(&ManeuverHandle, &mut Transform, &mut Visibility)>,
+) {
+    let scale = camera.single().scale;
+    let point = maneuver
+        .0
+        .and_then(|node| node.point(ship_prediction.single()));
+
+    let (mut marker_transform, mut marker_visibility) = node_marker.single_mut();
+    let Some(point) = point else {
+        *marker_visibility = Visibility::Hidden;
+        for (_, _, mut visibility) in handles.iter_mut() {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    };
+
+    // Keep everything the same size on screen, regardless of zoom.
+    *marker_visibility = Visibility::Visible;
+    *marker_transform =
+        Transform::from_translation(point.position.extend(3.0)).with_scale(Vec3::splat(scale));
+    for (handle, mut transform, mut visibility) in handles.iter_mut() {
+        let position = point.position + handle.axis(point.velocity) * HANDLE_DISTANCE * scale;
+        *visibility = Visibility::Visible;
+        *transform =
+            Transform::from_translation(position.extend(3.0)).with_scale(Vec3::splat(scale));
+    }
+}
+
+fn maneuver_ui_system(
+    maneuver: Res<PlannedManeuver>,
+    ship: Query<&Ship>,
+    mut text: Query<&mut Text, With<ManeuverText>>,
+) {
+    let mut text = text.single_mut();
+    let Some(node) = maneuver.0 else {
+        text.sections[0].value.clear();
+        return;
+    };
+
+    let Ok(ship) = ship.get_single() else {
+        return;
+    };
+    let fuel = ship.fuel;
+    let fuel_cost = node.fuel_cost();
+    let sign = if node.time >= 0.0 { '-' } else { '+' };
+    text.sections[0].value = format!(
+        "Burn dV {:.0} at T{sign}{:.1}s\nFuel {:.1}/{:.1}",
+        node.delta_v.length(),
+        node.time.abs(),
+        fuel_cost,
+        fuel,
+    );
+    text.sections[0].style.color = if fuel_cost <= fuel {
+        Color::WHITE
+    } else {
+        Color::rgb(0.9, 0.2, 0.1)
+    };
+}
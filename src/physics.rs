@@ -1,10 +1,30 @@
+mod atmosphere;
+mod barnes_hut;
+mod broad_phase;
 mod collision;
+mod dynamic_collision;
 mod gravity;
+mod integrator;
+mod orbit;
+mod orbital_elements;
+#[cfg(test)]
+mod random;
+mod shape;
+mod sphere_of_influence;
 
 use bevy::prelude::*;
 
+pub use atmosphere::*;
+pub use barnes_hut::*;
+pub use broad_phase::*;
 pub use collision::*;
+pub use dynamic_collision::*;
 pub use gravity::*;
+pub use integrator::*;
+pub use orbit::*;
+pub use orbital_elements::*;
+pub use shape::*;
+pub use sphere_of_influence::*;
 
 use crate::time::TimeScale;
 
@@ -12,11 +32,32 @@ pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(CollisionPlugin).add_systems(
-            FixedUpdate,
-            (velocity_system, gravity_system.before(velocity_system))
-                .in_set(PhysicsSet::PhysicsSet),
-        );
+        app.init_resource::<Integrator>()
+            .init_resource::<GravityMode>()
+            .init_resource::<GravityConfig>()
+            .add_plugins((
+                CollisionPlugin,
+                SphereOfInfluencePlugin,
+                BroadPhasePlugin,
+                AtmospherePlugin,
+                DynamicCollisionPlugin,
+            ))
+            .add_systems(
+                FixedUpdate,
+                (
+                    velocity_system,
+                    // Turn before collisions, so shapes are checked where
+                    // they are this tick, and the spin that collisions and
+                    // friction give is used from the next tick on.
+                    angular_velocity_system.before(collision_detection),
+                    gravity_system.before(velocity_system),
+                    orbit_system
+                        .after(velocity_system)
+                        .after(gravity_system)
+                        .before(collision_detection),
+                )
+                    .in_set(PhysicsSet::PhysicsSet),
+            );
     }
 }
 
@@ -31,13 +72,46 @@ pub struct Mass(pub f32);
 #[derive(Component, Default)]
 pub struct Velocity(pub Vec2);
 
+/// How fast an entity turns, in radians per second. Positive is
+/// counter-clockwise.
+#[derive(Component, Default)]
+pub struct AngularVelocity(pub f32);
+
+/// How hard it is to change an entity's `AngularVelocity`, like `Mass` is for
+/// `Velocity`.
+#[derive(Component)]
+pub struct MomentOfInertia(pub f32);
+
+impl MomentOfInertia {
+    /// The moment of inertia of a solid disk.
+    pub fn disk(mass: f32, radius: f32) -> Self {
+        Self(0.5 * mass * radius * radius)
+    }
+}
+
+/// Moves entities that have a velocity, but are not affected by gravity.
+/// Entities that are affected by gravity are moved by `gravity_system`, and
+/// entities on rails are moved by `orbit_system`.
 pub fn velocity_system(
     time: Res<FixedTime>,
     time_scale: Res<TimeScale>,
-    mut query: Query<(&Velocity, &mut Transform)>) {
+    mut query: Query<(&Velocity, &mut Transform), (Without<AffectedByGravity>, Without<Orbit>)>,
+) {
     query
         .par_iter_mut()
         .for_each_mut(|(velocity, mut transform)| {
             transform.translation += (velocity.0 * time_scale.delta_f32(&time)).extend(0.0);
         });
 }
+
+/// Turns entities by their angular velocity.
+pub fn angular_velocity_system(
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+    mut query: Query<(&AngularVelocity, &mut Transform)>,
+) {
+    let delta = time_scale.delta_f32(&time);
+    for (angular_velocity, mut transform) in query.iter_mut() {
+        transform.rotate_z(angular_velocity.0 * delta);
+    }
+}
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+
+use super::{gravity_system, Circle, Mass, Velocity};
+use crate::time::TimeScale;
+
+pub struct AtmospherePlugin;
+
+impl Plugin for AtmospherePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            drag_system
+                .before(gravity_system)
+                .in_set(super::PhysicsSet::PhysicsSet),
+        );
+    }
+}
+
+/// A layer of air around a body with a `Circle`.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Atmosphere {
+    /// How high above the surface the atmosphere goes.
+    pub height: f32,
+    /// The density of the air at the surface.
+    pub density: f32,
+    /// The height over which the density falls by a factor of `e`.
+    pub scale_height: f32,
+    /// The color of the sky at the surface.
+    pub sky_color: Color,
+}
+
+impl Atmosphere {
+    /// The density of the air at `altitude` above the surface.
+    pub fn density_at(&self, altitude: f32) -> f32 {
+        if altitude >= self.height {
+            return 0.0;
+        }
+        self.density * (-altitude.max(0.0) / self.scale_height).exp()
+    }
+}
+
+/// An entity that air slows down.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Drag {
+    /// The drag coefficient times the cross section area.
+    pub coefficient: f32,
+    /// How hard the air slowed the entity down in the last tick. Used for
+    /// effects.
+    pub deceleration: f32,
+}
+
+impl Drag {
+    pub fn new(coefficient: f32) -> Self {
+        Self {
+            coefficient,
+            deceleration: 0.0,
+        }
+    }
+}
+
+/// Slows down entities with `Drag` by the air of every atmosphere they are in,
+/// relative to the air's velocity.
+pub fn drag_system(
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+    atmospheres: Query<(&Atmosphere, &Transform, &Circle, Option<&Velocity>)>,
+    mut bodies: Query<(&mut Drag, &mut Velocity, &Transform, Option<&Mass>), Without<Atmosphere>>,
+) {
+    let delta = time_scale.delta_f32(&time);
+    for (mut drag, mut velocity, transform, mass) in bodies.iter_mut() {
+        let position = transform.translation.truncate();
+        let mass = mass.map_or(1.0, |m| m.0);
+        let mut deceleration = 0.0;
+
+        for (atmosphere, atmosphere_transform, circle, air_velocity) in atmospheres.iter() {
+            let center = atmosphere_transform.translation.truncate();
+            let altitude = position.distance(center) - circle.radius;
+            let density = atmosphere.density_at(altitude);
+            if density <= 0.0 {
+                continue;
+            }
+
+            let air_velocity = air_velocity.map_or(Vec2::ZERO, |v| v.0);
+            let relative_velocity = velocity.0 - air_velocity;
+            let speed = relative_velocity.length();
+            let acceleration = 0.5 * density * speed * speed * drag.coefficient / mass;
+            // Drag can stop us relative to the air, but never push us back.
+            let change = (acceleration * delta).min(speed);
+            velocity.0 -= relative_velocity.normalize_or_zero() * change;
+            deceleration += acceleration;
+        }
+
+        drag.deceleration = deceleration;
+    }
+}
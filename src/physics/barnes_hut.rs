@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+
+use super::{GravityConfig, GravitySourceState};
+
+/// The opening angle used when switching to `GravityMode::BarnesHut`.
+/// Smaller is more accurate, and `0.0` is the same as the exact sum.
+pub const DEFAULT_OPENING_ANGLE: f32 = 0.5;
+
+/// How many times a square can be split. Stops sources that are at the exact
+/// same position from splitting forever.
+const MAX_DEPTH: usize = 32;
+
+/// A quadtree of gravity sources, where every square knows it's total mass and
+/// center of mass. Squares that are far enough away pull as one body, so the
+/// pull on a point takes about `O(log N)` instead of `O(N)`.
+#[derive(Debug, Clone)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    center: Vec2,
+    half_size: f32,
+    mass: f32,
+    center_of_mass: Vec2,
+    contents: Contents,
+}
+
+#[derive(Debug, Clone)]
+enum Contents {
+    Empty,
+    /// The indices of the sources in this square. Only more than one at
+    /// `MAX_DEPTH`.
+    Sources(Vec<usize>),
+    /// The indices of the four child nodes.
+    Split([usize; 4]),
+}
+
+impl QuadTree {
+    pub fn new(affectors: &[GravitySourceState]) -> Self {
+        let (min, max) = affectors.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), affector| (min.min(affector.position), max.max(affector.position)),
+        );
+        let (center, half_size) = if affectors.is_empty() {
+            (Vec2::ZERO, 1.0)
+        } else {
+            (
+                (min + max) / 2.0,
+                ((max - min).max_element() / 2.0).max(1.0),
+            )
+        };
+
+        let mut tree = Self {
+            nodes: vec![Node::new(center, half_size)],
+        };
+        for index in 0..affectors.len() {
+            tree.insert(affectors, 0, index, 0);
+        }
+        tree.compute_mass(affectors, 0);
+        tree
+    }
+
+    fn insert(
+        &mut self,
+        affectors: &[GravitySourceState],
+        node: usize,
+        index: usize,
+        depth: usize,
+    ) {
+        match &mut self.nodes[node].contents {
+            Contents::Empty => self.nodes[node].contents = Contents::Sources(vec![index]),
+            Contents::Sources(sources) if depth >= MAX_DEPTH => sources.push(index),
+            Contents::Sources(sources) => {
+                let sources = std::mem::take(sources);
+                let children = self.split(node);
+                self.nodes[node].contents = Contents::Split(children);
+                for source in sources.into_iter().chain([index]) {
+                    let child = children[self.nodes[node].quadrant(affectors[source].position)];
+                    self.insert(affectors, child, source, depth + 1);
+                }
+            }
+            &mut Contents::Split(children) => {
+                let child = children[self.nodes[node].quadrant(affectors[index].position)];
+                self.insert(affectors, child, index, depth + 1);
+            }
+        }
+    }
+
+    fn split(&mut self, node: usize) -> [usize; 4] {
+        let Node {
+            center, half_size, ..
+        } = self.nodes[node];
+        let quarter = half_size / 2.0;
+        [
+            Vec2::new(-quarter, -quarter),
+            Vec2::new(quarter, -quarter),
+            Vec2::new(-quarter, quarter),
+            Vec2::new(quarter, quarter),
+        ]
+        .map(|offset| {
+            self.nodes.push(Node::new(center + offset, quarter));
+            self.nodes.len() - 1
+        })
+    }
+
+    fn compute_mass(&mut self, affectors: &[GravitySourceState], node: usize) -> (f32, Vec2) {
+        let (mass, weighted_position) = match self.nodes[node].contents.clone() {
+            Contents::Empty => (0.0, Vec2::ZERO),
+            Contents::Sources(sources) => sources.iter().fold((0.0, Vec2::ZERO), |acc, &source| {
+                let affector = &affectors[source];
+                (
+                    acc.0 + affector.mass,
+                    acc.1 + affector.position * affector.mass,
+                )
+            }),
+            Contents::Split(children) => children.iter().fold((0.0, Vec2::ZERO), |acc, &child| {
+                let (mass, center_of_mass) = self.compute_mass(affectors, child);
+                (acc.0 + mass, acc.1 + center_of_mass * mass)
+            }),
+        };
+
+        let node = &mut self.nodes[node];
+        node.mass = mass;
+        node.center_of_mass = if mass > 0.0 {
+            weighted_position / mass
+        } else {
+            node.center
+        };
+        (node.mass, node.center_of_mass)
+    }
+
+    /// The acceleration at `point` caused by all `affectors` except for
+    /// `exclude`. `affectors` must be the ones the tree was built from.
+    pub fn acceleration(
+        &self,
+        config: &GravityConfig,
+        affectors: &[GravitySourceState],
+        exclude: Entity,
+        point: Vec2,
+        opening_angle: f32,
+    ) -> Vec2 {
+        let mut acceleration = Vec2::ZERO;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            match &node.contents {
+                Contents::Empty => {}
+                Contents::Sources(sources) => {
+                    for &source in sources {
+                        let affector = &affectors[source];
+                        if affector.entity == exclude {
+                            continue;
+                        }
+                        if let Ok(change_in_vel) =
+                            config.acceleration(affector.position - point, affector.mass)
+                        {
+                            acceleration += change_in_vel;
+                        }
+                    }
+                }
+                Contents::Split(children) => {
+                    // A square is far enough away to pull as one body when
+                    // it looks small enough from `point`. The center of mass
+                    // can be anywhere in the square, so how far it is from the
+                    // middle counts towards the size too. Squares we are in
+                    // might have `exclude` in them, so always open those.
+                    let relative_position = node.center_of_mass - point;
+                    let offset = node.center_of_mass.distance(node.center);
+                    let size = node.half_size * 2.0;
+                    let far_enough =
+                        size + opening_angle * offset < opening_angle * relative_position.length();
+                    if !node.contains(point) && far_enough {
+                        if let Ok(change_in_vel) = config.acceleration(relative_position, node.mass)
+                        {
+                            acceleration += change_in_vel;
+                        }
+                    } else {
+                        stack.extend_from_slice(children);
+                    }
+                }
+            }
+        }
+        acceleration
+    }
+}
+
+impl Node {
+    fn new(center: Vec2, half_size: f32) -> Self {
+        Self {
+            center,
+            half_size,
+            mass: 0.0,
+            center_of_mass: center,
+            contents: Contents::Empty,
+        }
+    }
+
+    /// Which child square `point` goes in, in the order `QuadTree::split`
+    /// makes them.
+    fn quadrant(&self, point: Vec2) -> usize {
+        let x = (point.x >= self.center.x) as usize;
+        let y = (point.y >= self.center.y) as usize;
+        x + 2 * y
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        (point - self.center).abs().max_element() <= self.half_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{get_total_gravity_acceleration, random::Random};
+
+    fn random_sources(random: &mut Random, count: u32) -> Vec<GravitySourceState> {
+        (0..count)
+            .map(|index| GravitySourceState {
+                entity: Entity::from_raw(index),
+                position: random.point(10_000.0),
+                mass: 1e3 + random.next() * 1e6,
+                sphere_of_influence: f32::INFINITY,
+            })
+            .collect()
+    }
+
+    /// Compares the pull on every source from all the others to the exact
+    /// sum. Returns the mean error relative to the exact pull, and the largest
+    /// error relative to the sum of the sizes of all the pulls. Where the
+    /// pulls almost cancel out, even a tiny error is big compared to the
+    /// exact pull, so the largest error is measured against the latter.
+    fn errors(opening_angle: f32) -> (f32, f32) {
+        let config = GravityConfig::default();
+        let affectors = random_sources(&mut Random(0x1234_5678), 500);
+        let tree = QuadTree::new(&affectors);
+
+        let mut total_relative_error = 0.0;
+        let mut max_scaled_error: f32 = 0.0;
+        for source in &affectors {
+            let exact =
+                get_total_gravity_acceleration(&config, &affectors, source.entity, source.position);
+            let approximate = tree.acceleration(
+                &config,
+                &affectors,
+                source.entity,
+                source.position,
+                opening_angle,
+            );
+            let scale: f32 = affectors
+                .iter()
+                .filter(|other| other.entity != source.entity)
+                .filter_map(|other| {
+                    config
+                        .acceleration(other.position - source.position, other.mass)
+                        .ok()
+                })
+                .map(Vec2::length)
+                .sum();
+
+            let error = approximate.distance(exact);
+            total_relative_error += error / exact.length();
+            max_scaled_error = max_scaled_error.max(error / scale);
+        }
+        (total_relative_error / affectors.len() as f32, max_scaled_error)
+    }
+
+    #[test]
+    fn close_to_the_exact_sum() {
+        let (mean_error, max_error) = errors(DEFAULT_OPENING_ANGLE);
+        assert!(mean_error < 0.02, "mean relative error {mean_error}");
+        assert!(max_error < 0.02, "largest error {max_error} of the pulls");
+    }
+
+    #[test]
+    fn zero_opening_angle_is_the_exact_sum() {
+        let (mean_error, max_error) = errors(0.0);
+        assert!(mean_error < 1e-5, "mean relative error {mean_error}");
+        assert!(max_error < 1e-6, "largest error {max_error} of the pulls");
+    }
+}
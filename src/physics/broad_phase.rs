@@ -0,0 +1,390 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    collision_detection, gravity_system, orbit_system, velocity_system, Circle, Collider,
+    Collision, GravityConfig, GravitySource, Mass, PreviousPosition,
+};
+
+pub struct BroadPhasePlugin;
+
+impl Plugin for BroadPhasePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BroadPhase>().add_systems(
+            FixedUpdate,
+            broad_phase_system
+                .after(velocity_system)
+                .after(gravity_system)
+                .after(orbit_system)
+                .before(collision_detection)
+                .in_set(super::PhysicsSet::PhysicsSet),
+        );
+    }
+}
+
+/// How big each cell of the broad phase grid is. Should be around the size
+/// of the smaller bodies, so that they only take up a few cells.
+const CELL_SIZE: f32 = 1024.0;
+
+/// A body in the broad phase.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BroadPhaseEntry {
+    pub entity: Entity,
+    pub position: Vec2,
+    /// `0.0` for bodies without a `Circle`.
+    pub radius: f32,
+    /// `0.0` for bodies that aren't gravity sources.
+    pub mass: f32,
+    pub collider: bool,
+    /// Whether it's a `Collision`, which moves and bumps into other ones.
+    pub dynamic: bool,
+}
+
+/// A uniform grid of the colliders, collisions and gravity sources, rebuilt
+/// every physics tick. Lets us only look at the bodies near a point, instead
+/// of all of them.
+#[derive(Resource, Debug, Default)]
+pub struct BroadPhase {
+    entries: Vec<BroadPhaseEntry>,
+    cells: HashMap<IVec2, Vec<usize>>,
+    /// The cells that have anything in them, inclusive.
+    bounds: Option<(IVec2, IVec2)>,
+    max_mass: f32,
+}
+
+impl BroadPhase {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.clear();
+        self.bounds = None;
+        self.max_mass = 0.0;
+    }
+
+    /// Adds a body to every cell in the box between `min` and `max`.
+    pub fn insert(&mut self, entry: BroadPhaseEntry, min: Vec2, max: Vec2) {
+        let index = self.entries.len();
+        self.entries.push(entry);
+        self.max_mass = self.max_mass.max(entry.mass);
+
+        let (min_cell, max_cell) = (cell_of(min), cell_of(max));
+        self.bounds = Some(match self.bounds {
+            Some((bounds_min, bounds_max)) => (bounds_min.min(min_cell), bounds_max.max(max_cell)),
+            None => (min_cell, max_cell),
+        });
+        for y in min_cell.y..=max_cell.y {
+            for x in min_cell.x..=max_cell.x {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    pub fn entries(&self) -> &[BroadPhaseEntry] {
+        &self.entries
+    }
+
+    /// The bodies that might be in the box between `min` and `max`. Each body
+    /// is returned once.
+    pub fn query_box(&self, min: Vec2, max: Vec2) -> Vec<&BroadPhaseEntry> {
+        let (min_cell, max_cell) = (cell_of(min), cell_of(max));
+        let mut indices = Vec::new();
+        for y in min_cell.y..=max_cell.y {
+            for x in min_cell.x..=max_cell.x {
+                if let Some(cell) = self.cells.get(&IVec2::new(x, y)) {
+                    indices.extend_from_slice(cell);
+                }
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .into_iter()
+            .map(|index| &self.entries[index])
+            .collect()
+    }
+
+    /// The gravity source whose center is closest to `point`, and the distance
+    /// to it's center.
+    pub fn nearest_source(&self, point: Vec2) -> Option<(&BroadPhaseEntry, f32)> {
+        self.best_near(
+            point,
+            |entry| (entry.mass > 0.0).then(|| entry.position.distance(point)),
+            |distance| distance,
+        )
+    }
+
+    /// The gravity source that pulls hardest on `point`, and the squared
+    /// acceleration it causes.
+    pub fn strongest_pull(
+        &self,
+        config: &GravityConfig,
+        point: Vec2,
+    ) -> Option<(&BroadPhaseEntry, f32)> {
+        let max_mu = config.gravitational_parameter(self.max_mass);
+        self.best_near(
+            point,
+            |entry| {
+                if entry.mass <= 0.0 {
+                    return None;
+                }
+                let acceleration = config.acceleration(entry.position - point, entry.mass).ok()?;
+                Some(-acceleration.length_squared())
+            },
+            // Nothing further than `distance` can pull harder than the heaviest
+            // body would from there, without softening.
+            |distance| -(max_mu / distance.powf(config.falloff)).powi(2),
+        )
+        .map(|(entry, score)| (entry, -score))
+    }
+
+    /// Searches the cells in growing rings around `point` for the entry with
+    /// the lowest score, until `bound(distance)` says that nothing further
+    /// than `distance` could beat the best one found.
+    fn best_near(
+        &self,
+        point: Vec2,
+        score: impl Fn(&BroadPhaseEntry) -> Option<f32>,
+        bound: impl Fn(f32) -> f32,
+    ) -> Option<(&BroadPhaseEntry, f32)> {
+        let (bounds_min, bounds_max) = self.bounds?;
+        let center = cell_of(point);
+        let max_ring = (center - bounds_min)
+            .abs()
+            .max((center - bounds_max).abs())
+            .max_element();
+
+        let mut best: Option<(usize, f32)> = None;
+        let mut cells_visited = 0;
+        for ring in 0..=max_ring {
+            // Far away from everything, it's faster to just check everything.
+            if cells_visited > self.entries.len() {
+                return self.best_of_all(score);
+            }
+
+            for cell in ring_cells(center, ring) {
+                cells_visited += 1;
+                let Some(indices) = self.cells.get(&cell) else {
+                    continue;
+                };
+                for &index in indices {
+                    let Some(entry_score) = score(&self.entries[index]) else {
+                        continue;
+                    };
+                    if best.is_none_or(|(_, best_score)| entry_score < best_score) {
+                        best = Some((index, entry_score));
+                    }
+                }
+            }
+
+            // Anything we haven't seen yet is at least this far.
+            let distance = ring as f32 * CELL_SIZE;
+            if let Some((_, best_score)) = best {
+                if best_score <= bound(distance) {
+                    break;
+                }
+            }
+        }
+
+        best.map(|(index, best_score)| (&self.entries[index], best_score))
+    }
+
+    fn best_of_all(
+        &self,
+        score: impl Fn(&BroadPhaseEntry) -> Option<f32>,
+    ) -> Option<(&BroadPhaseEntry, f32)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| Some((entry, score(entry)?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+fn cell_of(point: Vec2) -> IVec2 {
+    (point / CELL_SIZE).floor().as_ivec2()
+}
+
+/// The cells at exactly `ring` cells away from `center`.
+fn ring_cells(center: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    (-ring..=ring).flat_map(move |y| {
+        let on_edge = y.abs() == ring;
+        let step = if on_edge {
+            1
+        } else {
+            (2 * ring).max(1) as usize
+        };
+        (-ring..=ring)
+            .step_by(step)
+            .map(move |x| center + IVec2::new(x, y))
+    })
+}
+
+pub fn broad_phase_system(
+    mut broad_phase: ResMut<BroadPhase>,
+    bodies: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&PreviousPosition>,
+            Option<&Circle>,
+            Option<&Mass>,
+            Option<&Collider>,
+            Option<&GravitySource>,
+            Option<&Collision>,
+        ),
+        Or<(With<Collider>, With<GravitySource>, With<Collision>)>,
+    >,
+) {
+    broad_phase.clear();
+    for (entity, transform, previous_position, circle, mass, collider, gravity_source, collision) in
+        bodies.iter()
+    {
+        let position = transform.translation.truncate();
+        let previous_position = previous_position.map_or(position, |p| p.0);
+        let radius = circle.map_or(0.0, |c| c.radius);
+        let entry = BroadPhaseEntry {
+            entity,
+            position,
+            radius,
+            mass: match (gravity_source, mass) {
+                (Some(_), Some(mass)) => mass.0,
+                _ => 0.0,
+            },
+            collider: collider.is_some(),
+            dynamic: collision.is_some(),
+        };
+
+        // Cover the whole way the body moved this tick, for swept collisions.
+        let min = position.min(previous_position) - Vec2::splat(radius);
+        let max = position.max(previous_position) + Vec2::splat(radius);
+        broad_phase.insert(entry, min, max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::physics::random::Random;
+
+    const BODIES: u32 = 5000;
+    const LEVEL_SIZE: f32 = 200_000.0;
+
+    fn random_broad_phase(random: &mut Random, bodies: u32) -> BroadPhase {
+        let mut broad_phase = BroadPhase::default();
+        for index in 0..bodies {
+            let position = random.point(LEVEL_SIZE);
+            let radius = 10.0 + random.next() * 190.0;
+            // Every other body is a gravity source.
+            let mass = if index % 2 == 0 { radius.powi(3) } else { 0.0 };
+            let entry = BroadPhaseEntry {
+                entity: Entity::from_raw(index),
+                position,
+                radius,
+                mass,
+                collider: true,
+                dynamic: false,
+            };
+            broad_phase.insert(entry, position - radius, position + radius);
+        }
+        broad_phase
+    }
+
+    #[test]
+    fn queries_match_brute_force_with_thousands_of_bodies() {
+        let mut random = Random(0x2545_f491);
+        let broad_phase = random_broad_phase(&mut random, BODIES);
+        let config = GravityConfig::default();
+        let sources = || broad_phase.entries().iter().filter(|entry| entry.mass > 0.0);
+
+        for _ in 0..200 {
+            let point = random.point(LEVEL_SIZE);
+
+            let (nearest, distance) = broad_phase.nearest_source(point).unwrap();
+            let expected = sources()
+                .map(|entry| entry.position.distance(point))
+                .fold(f32::INFINITY, f32::min);
+            assert_eq!(distance, expected, "{:?}", nearest.entity);
+
+            let (_, pull) = broad_phase.strongest_pull(&config, point).unwrap();
+            let expected = sources()
+                .filter_map(|entry| config.acceleration(entry.position - point, entry.mass).ok())
+                .map(Vec2::length_squared)
+                .fold(0.0, f32::max);
+            assert_eq!(pull, expected);
+        }
+    }
+
+    #[test]
+    fn queries_only_look_at_nearby_bodies() {
+        let mut random = Random(0x9e37_79b9);
+        let broad_phase = random_broad_phase(&mut random, BODIES);
+        const QUERIES: u32 = 200;
+
+        let scored = Cell::new(0);
+        for _ in 0..QUERIES {
+            let point = random.point(LEVEL_SIZE);
+            broad_phase.best_near(
+                point,
+                |entry| {
+                    scored.set(scored.get() + 1);
+                    (entry.mass > 0.0).then(|| entry.position.distance(point))
+                },
+                |distance| distance,
+            );
+        }
+
+        // A brute force search would look at every body every time.
+        let average = scored.get() / QUERIES;
+        assert!(average * 50 < BODIES, "looked at {average} bodies per query");
+    }
+
+    /// Times box queries against checking every body, from a thousand bodies
+    /// up to ten thousand. Run it with
+    /// `cargo test --release -- --ignored --nocapture query_box`.
+    #[test]
+    #[ignore = "timing, run by hand"]
+    fn query_box_beats_brute_force_with_thousands_of_bodies() {
+        const QUERIES: usize = 1000;
+        // About the box a ship sweeps in one tick.
+        const BOX_SIZE: f32 = 100.0;
+
+        for bodies in [1_000, 2_000, 5_000, 10_000] {
+            let mut random = Random(0x2545_f491);
+            let broad_phase = random_broad_phase(&mut random, bodies);
+            let boxes: Vec<_> = (0..QUERIES)
+                .map(|_| {
+                    let min = random.point(LEVEL_SIZE);
+                    (min, min + BOX_SIZE)
+                })
+                .collect();
+
+            let mut grid = Duration::ZERO;
+            let mut brute_force = Duration::ZERO;
+            for &(min, max) in &boxes {
+                let start = Instant::now();
+                let found = broad_phase.query_box(min, max);
+                grid += start.elapsed();
+
+                let start = Instant::now();
+                let expected: Vec<_> = broad_phase
+                    .entries()
+                    .iter()
+                    .filter(|entry| {
+                        (entry.position + entry.radius).cmpge(min).all()
+                            && (entry.position - entry.radius).cmple(max).all()
+                    })
+                    .collect();
+                brute_force += start.elapsed();
+
+                // The grid can return bodies in the same cells that don't
+                // touch the box, but never misses one.
+                for entry in expected {
+                    assert!(found.contains(&entry), "{:?} missing", entry.entity);
+                }
+            }
+
+            println!("{bodies} bodies: grid {grid:?}, brute force {brute_force:?}");
+            assert!(grid < brute_force, "{bodies} bodies: {grid:?} >= {brute_force:?}");
+        }
+    }
+}
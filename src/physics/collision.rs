@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
-use super::{velocity_system, Velocity};
+use super::{
+    gravity_system, shape_contact, velocity_system, AngularVelocity, BroadPhase, Mass,
+    MomentOfInertia, RoundedCore, Shape, Velocity,
+};
 
 pub struct CollisionPlugin;
 
@@ -9,6 +12,9 @@ impl Plugin for CollisionPlugin {
         app.add_event::<CollisionEvent>().add_systems(
             FixedUpdate,
             (
+                previous_position_system
+                    .before(velocity_system)
+                    .before(gravity_system),
                 collision_detection.after(velocity_system),
                 collision_resolution.after(collision_detection),
             ).in_set(super::PhysicsSet::PhysicsSet),
@@ -29,69 +35,551 @@ pub struct Collision;
 #[derive(Component, Default)]
 pub struct Collider;
 
+/// Where an entity was at the start of the current physics tick. Collisions
+/// are checked along the whole way from here, so fast entities can't tunnel
+/// through colliders.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct PreviousPosition(pub Vec2);
+
 #[derive(Event)]
 pub struct CollisionEvent {
     pub collision_entity: Entity,
     pub collider_entity: Entity,
     pub normal: Vec2,
+    /// The point on the collider's surface, at the time of impact.
     pub point: Vec2,
+    /// How deep the exact shapes overlap, when either of them has a `Shape`.
+    /// These are pushed out along the normal by this much, instead of being
+    /// put on the collider's circle.
+    pub penetration: Option<f32>,
+    /// How fast the entity was moving relative to the collider when they hit,
+    /// if `dynamic_collision_system` already resolved the collision. Those
+    /// are only sent so the impact can be told about, and
+    /// `collision_resolution` leaves them alone.
+    pub impact_velocity: Option<Vec2>,
+}
+
+/// Records where everything that takes part in collisions is, before the
+/// physics moves it.
+pub fn previous_position_system(
+    mut commands: Commands,
+    mut query: Query<
+        (Entity, &Transform, Option<&mut PreviousPosition>),
+        Or<(With<Collision>, With<Collider>)>,
+    >,
+) {
+    for (entity, transform, previous_position) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        match previous_position {
+            Some(mut previous_position) => previous_position.0 = position,
+            None => {
+                commands.entity(entity).insert(PreviousPosition(position));
+            }
+        }
+    }
 }
 
 pub fn collision_detection(
-    collisions: Query<(Entity, &Transform, Option<&Circle>), With<Collision>>,
-    colliders: Query<(Entity, &Transform, &Circle), With<Collider>>,
+    collisions: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&PreviousPosition>,
+            Option<&Circle>,
+            Option<&Shape>,
+        ),
+        With<Collision>,
+    >,
+    colliders: Query<(&Transform, Option<&PreviousPosition>, Option<&Shape>), With<Collider>>,
+    broad_phase: Res<BroadPhase>,
     mut events: EventWriter<CollisionEvent>,
 ) {
-    for (collision_entity, collision_transform, collision_circle) in collisions.iter() {
-        for (collider_entity, collider_transform, collider_circle) in colliders.iter() {
+    for (collision_entity, collision_transform, collision_previous, collision_circle, collision_shape) in
+        collisions.iter()
+    {
+        let body = CollisionBody::new(
+            collision_transform,
+            collision_previous,
+            collision_circle.map_or(0., |c| c.radius),
+            collision_shape,
+        );
+
+        // Only check the colliders near the way we moved this tick.
+        let (min, max) = body.swept_bounds();
+        for collider in broad_phase.query_box(min, max) {
             // Don't collide with yourself!
-            if collision_entity == collider_entity {
+            if !collider.collider || collision_entity == collider.entity {
                 continue;
             }
+            let Ok((collider_transform, collider_previous, collider_shape)) =
+                colliders.get(collider.entity)
+            else {
+                continue;
+            };
+            let collider_body = CollisionBody::new(
+                collider_transform,
+                collider_previous,
+                collider.radius,
+                collider_shape,
+            );
 
-            let collision_position = collision_transform.translation.truncate();
-            let collider_position = collider_transform.translation.truncate();
-            let distance_squared = collision_position.distance_squared(collider_position);
-            let min_distance = collision_circle.map_or(0., |c| c.radius) + collider_circle.radius;
-            let min_distance_squared = min_distance * min_distance;
-
-            if distance_squared <= min_distance_squared {
-                let collider_to_collision = collision_position - collider_position;
-                let normal = collider_to_collision.normalize_or_zero();
-
-                let point = collider_position + normal * collider_circle.radius;
-
+            if let Some(contact) = body_contact(&body, &collider_body) {
                 events.send(CollisionEvent {
                     collision_entity,
-                    collider_entity,
-                    normal,
-                    point,
+                    collider_entity: collider.entity,
+                    normal: contact.normal,
+                    point: contact.point,
+                    penetration: contact.penetration,
+                    impact_velocity: None,
                 });
             }
         }
     }
 }
 
+/// Everything about an entity that is needed to check it's collisions.
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionBody<'a> {
+    pub transform: &'a Transform,
+    pub previous_position: Vec2,
+    /// The radius of the `Circle`, which bounds the `Shape` if there is one.
+    pub radius: f32,
+    pub shape: Option<&'a Shape>,
+}
+
+impl<'a> CollisionBody<'a> {
+    pub fn new(
+        transform: &'a Transform,
+        previous_position: Option<&PreviousPosition>,
+        radius: f32,
+        shape: Option<&'a Shape>,
+    ) -> Self {
+        Self {
+            transform,
+            previous_position: previous_position
+                .map_or(transform.translation.truncate(), |p| p.0),
+            radius,
+            shape,
+        }
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.transform.translation.truncate()
+    }
+
+    /// The box around everywhere the body was this tick.
+    pub fn swept_bounds(&self) -> (Vec2, Vec2) {
+        let position = self.position();
+        (
+            position.min(self.previous_position) - Vec2::splat(self.radius),
+            position.max(self.previous_position) + Vec2::splat(self.radius),
+        )
+    }
+
+    fn rounded_core(&self) -> RoundedCore {
+        match self.shape {
+            Some(shape) => shape.rounded_core(self.transform),
+            None => RoundedCore::circle(self.position(), self.radius),
+        }
+    }
+}
+
+/// Where `body` touched `collider` this tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BodyContact {
+    /// The normal pointing out of the collider.
+    pub normal: Vec2,
+    /// The point on the collider's surface.
+    pub point: Vec2,
+    /// How deep the exact shapes overlap, when either has a `Shape`.
+    pub penetration: Option<f32>,
+}
+
+/// Checks if `body` touched `collider` at any point this tick.
+pub fn body_contact(body: &CollisionBody, collider: &CollisionBody) -> Option<BodyContact> {
+    let contact = swept_circle_contact(
+        body.previous_position,
+        body.position(),
+        body.radius,
+        collider.previous_position,
+        collider.position(),
+        collider.radius,
+    )?;
+
+    // The bounding circles touched. If they still overlap, check the exact
+    // shapes. If they don't, we went right through, so stop at the bounding
+    // circle.
+    let overlapping = circle_contact(
+        body.position(),
+        body.radius,
+        collider.position(),
+        collider.radius,
+    )
+    .is_some();
+    if overlapping && (body.shape.is_some() || collider.shape.is_some()) {
+        let contact = shape_contact(&body.rounded_core(), &collider.rounded_core())?;
+        return Some(BodyContact {
+            normal: contact.normal,
+            point: contact.point,
+            penetration: Some(contact.depth),
+        });
+    }
+
+    Some(BodyContact {
+        normal: contact.normal,
+        point: contact.point,
+        penetration: None,
+    })
+}
+
+/// Where two moving circles first touched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweptContact {
+    /// The normal pointing out of the collider.
+    pub normal: Vec2,
+    /// The point on the collider's surface.
+    pub point: Vec2,
+}
+
+/// Checks if a circle moving from `start` to `end` touches a collider circle
+/// moving from `collider_start` to `collider_end` at any point along the way,
+/// assuming both move in straight lines. If they were already overlapping at
+/// the start, reports the overlap at the end instead, like `circle_contact`.
+pub fn swept_circle_contact(
+    start: Vec2,
+    end: Vec2,
+    radius: f32,
+    collider_start: Vec2,
+    collider_end: Vec2,
+    collider_radius: f32,
+) -> Option<SweptContact> {
+    let min_distance = radius + collider_radius;
+
+    // Work in the collider's frame, where only the circle moves.
+    let relative_start = start - collider_start;
+    let relative_movement = (end - collider_end) - relative_start;
+
+    if relative_start.length_squared() <= min_distance * min_distance {
+        let (normal, point) = circle_contact(end, radius, collider_end, collider_radius)?;
+        return Some(SweptContact { normal, point });
+    }
+
+    let a = relative_movement.length_squared();
+    if a == 0.0 {
+        return None;
+    }
+
+    // Find where |relative_start + t * relative_movement| = min_distance.
+    // Solving the quadratic directly loses all precision when the movement is
+    // much longer than the colliders are big, so go through the closest
+    // approach instead, which only depends on the sideways offset.
+    let closest = relative_movement.perp() * relative_movement.perp_dot(relative_start) / a;
+    let overlap_squared = min_distance * min_distance - closest.length_squared();
+    if overlap_squared < 0.0 {
+        return None;
+    }
+    // Back off from the closest approach to where they first touch.
+    let touching = closest - relative_movement / a.sqrt() * overlap_squared.sqrt();
+    let time = (touching - relative_start).dot(relative_movement) / a;
+    if !(0.0..=1.0).contains(&time) {
+        return None;
+    }
+
+    let normal = touching.normalize_or_zero();
+    let collider_position = collider_start.lerp(collider_end, time);
+    Some(SweptContact {
+        normal,
+        point: collider_position + normal * collider_radius,
+    })
+}
+
+/// Checks if a circle overlaps a collider circle. If it does, returns the
+/// normal pointing out of the collider, and the point on the collider's
+/// surface.
+pub fn circle_contact(
+    position: Vec2,
+    radius: f32,
+    collider_position: Vec2,
+    collider_radius: f32,
+) -> Option<(Vec2, Vec2)> {
+    let distance_squared = position.distance_squared(collider_position);
+    let min_distance = radius + collider_radius;
+    let min_distance_squared = min_distance * min_distance;
+
+    if distance_squared > min_distance_squared {
+        return None;
+    }
+
+    let collider_to_collision = position - collider_position;
+    let normal = collider_to_collision.normalize_or_zero();
+    let point = collider_position + normal * collider_radius;
+    Some((normal, point))
+}
+
+/// Hitting a surface slower than this doesn't bounce, so resting entities
+/// don't jitter.
+const MIN_BOUNCE_SPEED: f32 = 5.0;
+/// Moving along a surface slower than this comes to rest on it.
+const REST_SPEED: f32 = 1.0;
+
+/// How a collider's surface reacts to things hitting it.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsMaterial {
+    /// How much of the speed into the surface slows down sliding along it.
+    pub friction: f32,
+    /// How much of the speed into the surface is kept when bouncing off.
+    pub restitution: f32,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            friction: 0.8,
+            restitution: 0.0,
+        }
+    }
+}
+
+/// An entity resting on a collider's surface. It sticks to the surface, and
+/// moves and spins with it, until it takes off.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct RestingContact {
+    pub collider_entity: Entity,
+    /// Where the entity is relative to the collider, in the collider's frame.
+    pub local_offset: Vec2,
+}
+
+/// The speed an entity hitting a surface at `speed` bounces off with.
+pub fn bounce_speed(speed: f32, restitution: f32) -> f32 {
+    if speed > MIN_BOUNCE_SPEED {
+        speed * restitution
+    } else {
+        0.0
+    }
+}
+
 pub fn collision_resolution(
+    mut commands: Commands,
     mut collision_events: EventReader<CollisionEvent>,
     mut collisions: Query<
-        (&mut Transform, Option<&mut Velocity>, Option<&Circle>),
+        (
+            &mut Transform,
+            Option<&mut Velocity>,
+            Option<&Circle>,
+            Option<&RestingContact>,
+            Option<&Mass>,
+            Option<(&mut AngularVelocity, &MomentOfInertia)>,
+        ),
         With<Collision>,
     >,
+    colliders: Query<
+        (
+            &Transform,
+            &Circle,
+            Option<&Velocity>,
+            Option<&PhysicsMaterial>,
+            Option<&AngularVelocity>,
+        ),
+        (With<Collider>, Without<Collision>),
+    >,
+    resting: Query<Entity, With<RestingContact>>,
 ) {
+    let mut touching = Vec::new();
     for event in collision_events.iter() {
-        let (mut collision_transform, collision_velocity, collision_circle) =
-            collisions.get_mut(event.collision_entity).unwrap();
+        if event.impact_velocity.is_some() {
+            continue;
+        }
+        let (
+            mut collision_transform,
+            collision_velocity,
+            collision_circle,
+            resting_contact,
+            mass,
+            mut rotation,
+        ) = collisions.get_mut(event.collision_entity).unwrap();
+        touching.push(event.collision_entity);
 
         let radius = collision_circle.map_or(0., |c| c.radius);
+        let collider = colliders.get(event.collider_entity).ok();
+
+        // Move the collision entity out! Exact shapes are pushed out of each
+        // other. Circles are put back on the collider, which may have moved on
+        // since the impact, at where the impact is on the collider now.
+        let arm = match event.penetration {
+            Some(penetration) => {
+                collision_transform.translation += (event.normal * penetration).extend(0.0);
+                event.point - collision_transform.translation.truncate()
+            }
+            None => {
+                let point = match collider {
+                    Some((collider_transform, collider_circle, ..)) => {
+                        collider_transform.translation.truncate()
+                            + event.normal * collider_circle.radius
+                    }
+                    None => event.point,
+                };
+                collision_transform.translation = (point + event.normal * radius).extend(0.0);
+                -event.normal * radius
+            }
+        };
 
-        // Move the collision entity out!
-        collision_transform.translation = (event.point + event.normal * radius).extend(0.0);
+        let Some(mut velocity) = collision_velocity else {
+            continue;
+        };
 
-        // And update it's velocity.
-        if let Some(mut velocity) = collision_velocity {
-            let amount_to_remove = velocity.0.dot(-event.normal).max(0.);
-            velocity.0 += amount_to_remove * event.normal;
+        // And update it's velocity, relative to the surface.
+        let collider_velocity = collider.and_then(|(_, _, v, ..)| v).map_or(Vec2::ZERO, |v| v.0);
+        let material = collider
+            .and_then(|(_, _, _, material, _)| material.copied())
+            .unwrap_or_default();
+        let mut relative_velocity = velocity.0 - collider_velocity;
+
+        let mass = mass.map_or(1.0, |m| m.0);
+        let speed_into_surface = relative_velocity.dot(-event.normal).max(0.);
+        let bounce = bounce_speed(speed_into_surface, material.restitution);
+        relative_velocity += (speed_into_surface + bounce) * event.normal;
+
+        // Hitting off-center, like corner first, spins entities that can turn.
+        if let Some((angular_velocity, inertia)) = &mut rotation {
+            let impulse = (speed_into_surface + bounce) * mass;
+            angular_velocity.0 += arm.perp_dot(event.normal) * impulse / inertia.0;
+        }
+
+        // Friction slows down sliding, harder the harder we push into the
+        // surface. It pushes on the contact point, not the center, so it
+        // also spins entities that can turn.
+        let spin_velocity = rotation
+            .as_ref()
+            .map_or(Vec2::ZERO, |(angular_velocity, _)| angular_velocity.0 * arm.perp());
+        let contact_velocity = relative_velocity + spin_velocity;
+        let sliding = contact_velocity - contact_velocity.dot(event.normal) * event.normal;
+        let direction = sliding.normalize_or_zero();
+        let leverage = arm.perp_dot(direction);
+        let inverse_inertia = rotation
+            .as_ref()
+            .map_or(0.0, |(_, inertia)| leverage * leverage / inertia.0);
+        let max_impulse = material.friction * speed_into_surface * mass;
+        let impulse = (sliding.length() / (1.0 / mass + inverse_inertia)).min(max_impulse);
+        relative_velocity -= direction * impulse / mass;
+        if let Some((angular_velocity, inertia)) = &mut rotation {
+            angular_velocity.0 -= leverage * impulse / inertia.0;
         }
+
+        if relative_velocity.length() >= REST_SPEED {
+            velocity.0 = collider_velocity + relative_velocity;
+            continue;
+        }
+
+        // Slow enough to rest on the surface.
+        velocity.0 = collider_velocity;
+        let Some((collider_transform, _, _, _, collider_angular_velocity)) = collider else {
+            continue;
+        };
+        if let Some((angular_velocity, _)) = &mut rotation {
+            angular_velocity.0 = collider_angular_velocity.map_or(0.0, |v| v.0);
+        }
+        let collider_position = collider_transform.translation.truncate();
+        match resting_contact {
+            Some(resting_contact) if resting_contact.collider_entity == event.collider_entity => {
+                let offset = collider_transform.rotation * resting_contact.local_offset.extend(0.0);
+                collision_transform.translation = (collider_position + offset.truncate()).extend(0.0);
+            }
+            _ => {
+                let offset = collision_transform.translation.truncate() - collider_position;
+                let local_offset = collider_transform.rotation.inverse() * offset.extend(0.0);
+                commands.entity(event.collision_entity).insert(RestingContact {
+                    collider_entity: event.collider_entity,
+                    local_offset: local_offset.truncate(),
+                });
+            }
+        }
+    }
+
+    // Entities that didn't touch anything this tick took off.
+    for entity in resting.iter() {
+        if !touching.contains(&entity) {
+            commands.entity(entity).remove::<RestingContact>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLANET_RADIUS: f32 = 1000.0;
+    const SHIP_RADIUS: f32 = 5.0;
+
+    /// Fires a ship straight through a planet at the origin in one tick, from
+    /// `distance` away on one side to `distance` away on the other.
+    fn fire_through_planet(distance: f32, offset: f32) -> Option<SweptContact> {
+        swept_circle_contact(
+            Vec2::new(-distance, offset),
+            Vec2::new(distance, offset),
+            SHIP_RADIUS,
+            Vec2::ZERO,
+            Vec2::ZERO,
+            PLANET_RADIUS,
+        )
+    }
+
+    #[test]
+    fn no_tunnelling_at_extreme_speeds() {
+        for distance in [2e3, 1e4, 1e5, 1e6, 1e7] {
+            let contact = fire_through_planet(distance, 0.0)
+                .unwrap_or_else(|| panic!("tunnelled through at {distance} per tick"));
+            assert!(contact.normal.distance(Vec2::NEG_X) < 1e-3);
+            assert!(
+                contact.point.distance(Vec2::new(-PLANET_RADIUS, 0.0)) < 1.0,
+                "hit at {} at {distance} per tick",
+                contact.point
+            );
+        }
+    }
+
+    #[test]
+    fn no_tunnelling_off_center_at_extreme_speeds() {
+        for distance in [2e3, 1e4, 1e5, 1e6, 1e7] {
+            let contact = fire_through_planet(distance, 900.0)
+                .unwrap_or_else(|| panic!("tunnelled through at {distance} per tick"));
+            assert!((contact.point.length() - PLANET_RADIUS).abs() < 1.0);
+            assert!(contact.point.x < 0.0 && contact.point.y > 0.0);
+        }
+    }
+
+    #[test]
+    fn grazing_misses_at_extreme_speeds() {
+        for distance in [2e3, 1e4, 1e5, 1e6, 1e7] {
+            let offset = PLANET_RADIUS + SHIP_RADIUS + 1.0;
+            assert!(fire_through_planet(distance, offset).is_none());
+        }
+    }
+
+    #[test]
+    fn stopping_short_misses() {
+        let contact = swept_circle_contact(
+            Vec2::new(-1e5, 0.0),
+            Vec2::new(-PLANET_RADIUS - SHIP_RADIUS - 1.0, 0.0),
+            SHIP_RADIUS,
+            Vec2::ZERO,
+            Vec2::ZERO,
+            PLANET_RADIUS,
+        );
+        assert!(contact.is_none());
+    }
+
+    #[test]
+    fn moving_planet_catches_a_resting_ship() {
+        // The planet moves through the ship, which stays where it is.
+        let contact = swept_circle_contact(
+            Vec2::ZERO,
+            Vec2::ZERO,
+            SHIP_RADIUS,
+            Vec2::new(-1e5, 0.0),
+            Vec2::new(1e5, 0.0),
+            PLANET_RADIUS,
+        )
+        .expect("the planet went through the ship");
+        assert!(contact.normal.distance(Vec2::X) < 1e-3);
     }
 }
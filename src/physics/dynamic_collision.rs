@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+
+use super::{
+    body_contact, broad_phase_system, collision_detection, BodyContact, BroadPhase, Circle,
+    Collision, CollisionBody, CollisionEvent, Mass, PhysicsMaterial, PreviousPosition, Shape,
+    Velocity,
+};
+use crate::player::Player;
+
+pub struct DynamicCollisionPlugin;
+
+impl Plugin for DynamicCollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            dynamic_collision_system
+                .after(broad_phase_system)
+                .before(collision_detection)
+                .in_set(super::PhysicsSet::PhysicsSet),
+        );
+    }
+}
+
+/// Lets entities that both collide (like ships and asteroids) bump into each
+/// other. Unlike with colliders, both of them move, by how heavy they are.
+/// Runs before `collision_detection`, so colliders still get the last word.
+/// The hidden player only collides with colliders, so the ship can't bump
+/// into it. Sends a `CollisionEvent` for each of the two, so impacts can be
+/// told about like ones with colliders.
+pub fn dynamic_collision_system(
+    broad_phase: Res<BroadPhase>,
+    mut bodies: Query<
+        (
+            &mut Transform,
+            Option<&PreviousPosition>,
+            &Circle,
+            Option<&Shape>,
+            &mut Velocity,
+            Option<&Mass>,
+            Option<&PhysicsMaterial>,
+        ),
+        (With<Collision>, Without<Player>),
+    >,
+    mut events: EventWriter<CollisionEvent>,
+) {
+    let dynamic = broad_phase
+        .entries()
+        .iter()
+        .filter(|entry| entry.dynamic)
+        .map(|entry| entry.entity)
+        .collect::<Vec<_>>();
+
+    for &entity in &dynamic {
+        let Ok((transform, previous_position, circle, shape, ..)) = bodies.get(entity) else {
+            continue;
+        };
+        let body = CollisionBody::new(transform, previous_position, circle.radius, shape);
+        let (min, max) = body.swept_bounds();
+
+        // Check every pair once.
+        let others = broad_phase
+            .query_box(min, max)
+            .into_iter()
+            .filter(|other| other.dynamic && other.entity > entity)
+            .map(|other| other.entity)
+            .collect::<Vec<_>>();
+        for other in others {
+            let Ok([first, second]) = bodies.get_many_mut([entity, other]) else {
+                continue;
+            };
+            let Some((contact, impact_velocity)) = resolve_pair(first, second) else {
+                continue;
+            };
+            events.send(CollisionEvent {
+                collision_entity: entity,
+                collider_entity: other,
+                normal: contact.normal,
+                point: contact.point,
+                penetration: contact.penetration,
+                impact_velocity: Some(impact_velocity),
+            });
+            events.send(CollisionEvent {
+                collision_entity: other,
+                collider_entity: entity,
+                normal: -contact.normal,
+                point: contact.point,
+                penetration: contact.penetration,
+                impact_velocity: Some(-impact_velocity),
+            });
+        }
+    }
+}
+
+type DynamicBody<'a> = (
+    Mut<'a, Transform>,
+    Option<&'a PreviousPosition>,
+    &'a Circle,
+    Option<&'a Shape>,
+    Mut<'a, Velocity>,
+    Option<&'a Mass>,
+    Option<&'a PhysicsMaterial>,
+);
+
+/// Pushes the two apart if they touch, and returns where they did and how
+/// fast the first was moving relative to the second before that.
+fn resolve_pair(first: DynamicBody, second: DynamicBody) -> Option<(BodyContact, Vec2)> {
+    let (mut transform, previous, circle, shape, mut velocity, mass, material) = first;
+    let (
+        mut other_transform,
+        other_previous,
+        other_circle,
+        other_shape,
+        mut other_velocity,
+        other_mass,
+        other_material,
+    ) = second;
+
+    let contact = body_contact(
+        &CollisionBody::new(&transform, previous, circle.radius, shape),
+        &CollisionBody::new(
+            &other_transform,
+            other_previous,
+            other_circle.radius,
+            other_shape,
+        ),
+    )?;
+    let impact_velocity = velocity.0 - other_velocity.0;
+
+    let mass = mass.map_or(1.0, |m| m.0);
+    let other_mass = other_mass.map_or(1.0, |m| m.0);
+    let total_mass = mass + other_mass;
+
+    // Push them apart, the lighter one more.
+    let depth = contact.penetration.unwrap_or_else(|| {
+        let along_normal = (transform.translation - other_transform.translation)
+            .truncate()
+            .dot(contact.normal);
+        circle.radius + other_circle.radius - along_normal
+    });
+    if depth > 0.0 {
+        let push = contact.normal * depth;
+        transform.translation += (push * other_mass / total_mass).extend(0.0);
+        other_transform.translation -= (push * mass / total_mass).extend(0.0);
+    }
+
+    // Exchange momentum along the normal, if they are moving into each other.
+    let speed_into = (other_velocity.0 - velocity.0).dot(contact.normal);
+    if speed_into <= 0.0 {
+        return Some((contact, impact_velocity));
+    }
+    let restitution = material
+        .copied()
+        .unwrap_or_default()
+        .restitution
+        .max(other_material.copied().unwrap_or_default().restitution);
+    let impulse = (1.0 + restitution) * speed_into / (1.0 / mass + 1.0 / other_mass);
+    velocity.0 += contact.normal * impulse / mass;
+    other_velocity.0 -= contact.normal * impulse / other_mass;
+    Some((contact, impact_velocity))
+}
@@ -1,4 +1,7 @@
-use super::{Mass, Velocity};
+use super::{
+    dominant_source, sphere_of_influence_radius, Integrator, Mass, Orbit, QuadTree, Velocity,
+    DEFAULT_OPENING_ANGLE,
+};
 use crate::time::TimeScale;
 use bevy::prelude::*;
 
@@ -8,39 +11,342 @@ pub struct GravitySource;
 #[derive(Component, Default)]
 pub struct AffectedByGravity;
 
+/// Which gravity sources pull on a body.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub enum GravityMode {
+    /// Every gravity source pulls on every body.
+    #[default]
+    Full,
+    /// Only the dominant body, whose sphere of influence the body is in,
+    /// pulls on it. Trajectories become conics that are patched together at
+    /// the edges of spheres of influence.
+    PatchedConics,
+    /// Every gravity source pulls on every body, but far away groups of
+    /// sources are approximated as one body using a Barnes–Hut quadtree. A
+    /// group is far enough away when it's size divided by it's distance is
+    /// less than `opening_angle`.
+    BarnesHut { opening_angle: f32 },
+}
+
+impl GravityMode {
+    pub fn next(self) -> Self {
+        match self {
+            GravityMode::Full => GravityMode::PatchedConics,
+            GravityMode::PatchedConics => GravityMode::BarnesHut {
+                opening_angle: DEFAULT_OPENING_ANGLE,
+            },
+            GravityMode::BarnesHut { .. } => GravityMode::Full,
+        }
+    }
+}
+
+/// Moves every entity affected by gravity, both it's velocity and it's
+/// position, using the selected `Integrator`. Gravity sources can be affected
+/// by gravity too, in which case they pull on each other (n-body).
 pub fn gravity_system(
     time: Res<FixedTime>,
     time_scale: Res<TimeScale>,
-    affectors: Query<(&Transform, &Mass), With<GravitySource>>,
-    mut affected: Query<(&mut Velocity, &Transform), With<AffectedByGravity>>,
+    integrator: Res<Integrator>,
+    mode: Res<GravityMode>,
+    config: Res<GravityConfig>,
+    mut bodies: ParamSet<(
+        GravitySourceQuery,
+        Query<(Entity, &mut Velocity, &mut Transform), With<AffectedByGravity>>,
+    )>,
 ) {
-    for (mut velocity, affected_transform) in affected.iter_mut() {
-        let affected_position = affected_transform.translation.truncate();
-        let acceleration = get_total_gravity_acceleration(&affectors, affected_position);
-        velocity.0 += acceleration * time_scale.delta_f32(&time);
+    let delta = time_scale.delta_f32(&time);
+    // Take a snapshot of the sources, so they all move at once.
+    let affectors = gravity_sources(&bodies.p0());
+    let field = GravityField::new(*mode, *config, &affectors);
+    for (entity, mut velocity, mut affected_transform) in bodies.p1().iter_mut() {
+        let (position, new_velocity) = step_body(
+            *integrator,
+            &field,
+            entity,
+            affected_transform.translation.truncate(),
+            velocity.0,
+            delta,
+        );
+        velocity.0 = new_velocity;
+        affected_transform.translation = position.extend(affected_transform.translation.z);
+    }
+}
+
+/// Advances the body `entity` by one step of gravity. This doesn't touch the
+/// ECS, so the trajectory prediction uses it too, and predicts exactly what
+/// the simulation will do.
+pub fn step_body(
+    integrator: Integrator,
+    field: &GravityField,
+    entity: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    delta: f32,
+) -> (Vec2, Vec2) {
+    if let Some((tree, opening_angle)) = &field.tree {
+        return integrator.step(position, velocity, delta, |point| {
+            tree.acceleration(&field.config, field.affectors, entity, point, *opening_angle)
+        });
+    }
+
+    let (affectors, frame_acceleration) =
+        affecting_sources(field.mode, &field.config, field.affectors, entity, position);
+    integrator.step(position, velocity, delta, |point| {
+        get_total_gravity_acceleration(&field.config, affectors, entity, point) + frame_acceleration
+    })
+}
+
+/// The gravity sources of one tick, with whatever the `GravityMode` needs
+/// prepared ahead of time, so it can be shared between all the bodies.
+pub struct GravityField<'a> {
+    mode: GravityMode,
+    config: GravityConfig,
+    affectors: &'a [GravitySourceState],
+    tree: Option<(QuadTree, f32)>,
+}
+
+impl<'a> GravityField<'a> {
+    pub fn new(
+        mode: GravityMode,
+        config: GravityConfig,
+        affectors: &'a [GravitySourceState],
+    ) -> Self {
+        let tree = match mode {
+            GravityMode::BarnesHut { opening_angle } => {
+                Some((QuadTree::new(affectors), opening_angle))
+            }
+            _ => None,
+        };
+        Self {
+            mode,
+            config,
+            affectors,
+            tree,
+        }
     }
 }
 
-fn get_total_gravity_acceleration(
-    affectors: &Query<(&Transform, &Mass), With<GravitySource>>,
+pub type GravitySourceQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static Mass,
+        Option<&'static Orbit>,
+    ),
+    With<GravitySource>,
+>;
+
+/// A snapshot of a gravity source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GravitySourceState {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub mass: f32,
+    /// Infinite for sources that don't orbit anything.
+    pub sphere_of_influence: f32,
+}
+
+pub fn gravity_sources(query: &GravitySourceQuery) -> Vec<GravitySourceState> {
+    query
+        .iter()
+        .map(|(entity, transform, mass, orbit)| {
+            let parent_mass = orbit.and_then(|orbit| query.get(orbit.parent).ok());
+            let sphere_of_influence = match (orbit, parent_mass) {
+                (Some(orbit), Some((_, _, parent_mass, _))) => {
+                    sphere_of_influence_radius(orbit.semi_major_axis, mass.0, parent_mass.0)
+                }
+                _ => f32::INFINITY,
+            };
+            GravitySourceState {
+                entity,
+                position: transform.translation.truncate(),
+                mass: mass.0,
+                sphere_of_influence,
+            }
+        })
+        .collect()
+}
+
+/// The sources that pull on the body `entity` at `point`, in the given mode,
+/// and an acceleration that pulls on it on top of them.
+///
+/// With patched conics that is the acceleration of the dominant body itself:
+/// a ship in a moon's sphere of influence falls towards the moon's parent
+/// along with the moon, instead of being left behind as the moon orbits.
+pub fn affecting_sources<'a>(
+    mode: GravityMode,
+    config: &GravityConfig,
+    affectors: &'a [GravitySourceState],
+    entity: Entity,
     point: Vec2,
+) -> (&'a [GravitySourceState], Vec2) {
+    match mode {
+        GravityMode::Full | GravityMode::BarnesHut { .. } => (affectors, Vec2::ZERO),
+        GravityMode::PatchedConics => match dominant_source(config, affectors, entity, point) {
+            Some(index) => (
+                std::slice::from_ref(&affectors[index]),
+                frame_acceleration(config, affectors, index),
+            ),
+            None => (&[], Vec2::ZERO),
+        },
+    }
+}
+
+/// The acceleration of the source at `index` with patched conics, which is
+/// the pull of it's dominant body, plus that body's own acceleration, up to a
+/// source that doesn't orbit anything.
+fn frame_acceleration(
+    config: &GravityConfig,
+    affectors: &[GravitySourceState],
+    mut index: usize,
 ) -> Vec2 {
     let mut acceleration = Vec2::ZERO;
-    for (affector_transform, affector_mass) in affectors.iter() {
-        let affector_position = affector_transform.translation.truncate();
-        let relative_position = affector_position - point;
-        if let Ok(change_in_vel) = get_gravity_acceleration(relative_position, affector_mass.0) {
+    // Bounded, in case the spheres of influence somehow form a loop.
+    for _ in 0..affectors.len() {
+        let source = &affectors[index];
+        if source.sphere_of_influence.is_infinite() {
+            break;
+        }
+        let Some(parent) = dominant_source(config, affectors, source.entity, source.position)
+        else {
+            break;
+        };
+        let parent_source = &affectors[parent];
+        if let Ok(pull) =
+            config.acceleration(parent_source.position - source.position, parent_source.mass)
+        {
+            acceleration += pull;
+        }
+        index = parent;
+    }
+    acceleration
+}
+
+/// The acceleration at `point` caused by all `affectors` except for `exclude`
+/// (so a body doesn't pull on itself).
+pub fn get_total_gravity_acceleration(
+    config: &GravityConfig,
+    affectors: &[GravitySourceState],
+    exclude: Entity,
+    point: Vec2,
+) -> Vec2 {
+    let mut acceleration = Vec2::ZERO;
+    for affector in affectors {
+        if affector.entity == exclude {
+            continue;
+        }
+        let relative_position = affector.position - point;
+        if let Ok(change_in_vel) = config.acceleration(relative_position, affector.mass) {
             acceleration += change_in_vel;
         }
     }
     acceleration
 }
 
-pub fn get_gravity_acceleration(relative_position: Vec2, mass: f32) -> Result<Vec2, ()> {
-    let distance = relative_position.length();
-    if distance == 0.0 {
-        return Err(());
+/// How gravity works in the current level. The level can override any of it,
+/// to make planets feel heavier or lighter, or to make puzzle levels with
+/// stranger gravity.
+///
+/// `Orbit`, `OrbitalElements` and spheres of influence assume inverse-square
+/// gravity without softening, so with anything else moons on rails and the
+/// predicted apses won't match what the ship actually does.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct GravityConfig {
+    /// `G`.
+    pub gravitational_constant: f32,
+    /// Gravity acts as if every body was at least this far away, so it stays
+    /// finite near the center of a body.
+    pub softening: f32,
+    /// The mass of a planet per cubed unit of radius, unless the planet sets
+    /// it's own.
+    pub density: f32,
+    /// The power of the distance that gravity falls off with. `2.0` is
+    /// inverse-square, like in reality.
+    pub falloff: f32,
+}
+
+impl Default for GravityConfig {
+    fn default() -> Self {
+        Self {
+            gravitational_constant: 0.5,
+            softening: 0.0,
+            density: 1.0,
+            falloff: 2.0,
+        }
     }
+}
 
-    Ok(0.5 * relative_position * mass / (distance * distance * distance))
+impl GravityConfig {
+    /// The mass of a planet with this radius, and `density` if it has it's
+    /// own.
+    pub fn mass(&self, radius: f32, density: Option<f32>) -> f32 {
+        density.unwrap_or(self.density) * radius * radius * radius
+    }
+
+    /// The standard gravitational parameter (`G * M`) of a body with this mass.
+    pub fn gravitational_parameter(&self, mass: f32) -> f32 {
+        self.gravitational_constant * mass
+    }
+
+    /// The acceleration towards a body of mass `mass` at `relative_position`
+    /// from us. Fails at the body's exact center.
+    pub fn acceleration(&self, relative_position: Vec2, mass: f32) -> Result<Vec2, ()> {
+        let distance_squared = relative_position.length_squared();
+        if distance_squared == 0.0 {
+            return Err(());
+        }
+
+        let softened_squared = distance_squared + self.softening * self.softening;
+        let scale = if self.falloff == 2.0 {
+            // The usual case, without the `powf`.
+            softened_squared * softened_squared.sqrt()
+        } else {
+            softened_squared.powf((self.falloff + 1.0) / 2.0)
+        };
+        Ok(relative_position * self.gravitational_parameter(mass) / scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patched_conics_adds_the_moons_own_acceleration() {
+        let config = GravityConfig::default();
+        let planet = GravitySourceState {
+            entity: Entity::from_raw(0),
+            position: Vec2::ZERO,
+            mass: 1000.0,
+            sphere_of_influence: f32::INFINITY,
+        };
+        let moon = GravitySourceState {
+            entity: Entity::from_raw(1),
+            position: Vec2::new(1000.0, 0.0),
+            mass: 10.0,
+            sphere_of_influence: sphere_of_influence_radius(1000.0, 10.0, 1000.0),
+        };
+        let affectors = [planet, moon];
+        let ship = Entity::from_raw(2);
+        let point = moon.position + Vec2::new(0.0, 50.0);
+
+        let (sources, frame_acceleration) =
+            affecting_sources(GravityMode::PatchedConics, &config, &affectors, ship, point);
+        assert_eq!(sources, &[moon]);
+        let moon_acceleration = config.acceleration(planet.position - moon.position, planet.mass);
+        assert_eq!(Ok(frame_acceleration), moon_acceleration);
+
+        // Outside of the moon's sphere, the planet is the frame.
+        let (sources, frame_acceleration) = affecting_sources(
+            GravityMode::PatchedConics,
+            &config,
+            &affectors,
+            ship,
+            Vec2::new(0.0, 500.0),
+        );
+        assert_eq!(sources, &[planet]);
+        assert_eq!(frame_acceleration, Vec2::ZERO);
+    }
 }
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+/// The numerical method used to advance bodies affected by gravity.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Integrator {
+    /// Position is advanced with the old velocity. Gains energy every step.
+    ExplicitEuler,
+    /// Velocity is advanced first, and the position is advanced with the new
+    /// velocity. Symplectic, and cheap.
+    #[default]
+    SemiImplicitEuler,
+    /// Leapfrog in kick-drift-kick form. Symplectic and second order.
+    VelocityVerlet,
+    /// Classic fourth order Runge-Kutta. Very accurate for short times, but
+    /// not symplectic, so energy slowly drifts over very long runs.
+    RungeKutta4,
+}
+
+impl Integrator {
+    /// The integrator after this one, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Integrator::ExplicitEuler => Integrator::SemiImplicitEuler,
+            Integrator::SemiImplicitEuler => Integrator::VelocityVerlet,
+            Integrator::VelocityVerlet => Integrator::RungeKutta4,
+            Integrator::RungeKutta4 => Integrator::ExplicitEuler,
+        }
+    }
+
+    /// Advances a body by `dt`, where `acceleration` gives the acceleration at
+    /// a point.
+    pub fn step(
+        self,
+        position: Vec2,
+        velocity: Vec2,
+        dt: f32,
+        acceleration: impl Fn(Vec2) -> Vec2,
+    ) -> (Vec2, Vec2) {
+        match self {
+            Integrator::ExplicitEuler => {
+                let a = acceleration(position);
+                (position + velocity * dt, velocity + a * dt)
+            }
+            Integrator::SemiImplicitEuler => {
+                let velocity = velocity + acceleration(position) * dt;
+                (position + velocity * dt, velocity)
+            }
+            Integrator::VelocityVerlet => {
+                let half_velocity = velocity + acceleration(position) * (dt / 2.0);
+                let position = position + half_velocity * dt;
+                let velocity = half_velocity + acceleration(position) * (dt / 2.0);
+                (position, velocity)
+            }
+            Integrator::RungeKutta4 => {
+                let k1_v = acceleration(position);
+                let k1_x = velocity;
+                let k2_v = acceleration(position + k1_x * (dt / 2.0));
+                let k2_x = velocity + k1_v * (dt / 2.0);
+                let k3_v = acceleration(position + k2_x * (dt / 2.0));
+                let k3_x = velocity + k2_v * (dt / 2.0);
+                let k4_v = acceleration(position + k3_x * dt);
+                let k4_x = velocity + k3_v * dt;
+
+                (
+                    position + (k1_x + 2.0 * k2_x + 2.0 * k3_x + k4_x) * (dt / 6.0),
+                    velocity + (k1_v + 2.0 * k2_v + 2.0 * k3_v + k4_v) * (dt / 6.0),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MU: f32 = 1000.0;
+    const RADIUS: f32 = 100.0;
+    const DT: f32 = 1.0 / 60.0;
+
+    fn acceleration(position: Vec2) -> Vec2 {
+        -MU * position / position.length().powi(3)
+    }
+
+    fn specific_energy(position: Vec2, velocity: Vec2) -> f32 {
+        velocity.length_squared() / 2.0 - MU / position.length()
+    }
+
+    /// The relative change in specific energy after `steps` steps on a
+    /// circular orbit.
+    fn energy_drift(integrator: Integrator, steps: usize) -> f32 {
+        let mut position = Vec2::new(RADIUS, 0.0);
+        let mut velocity = Vec2::new(0.0, (MU / RADIUS).sqrt());
+        let initial = specific_energy(position, velocity);
+        for _ in 0..steps {
+            (position, velocity) = integrator.step(position, velocity, DT, acceleration);
+        }
+        (specific_energy(position, velocity) - initial) / initial.abs()
+    }
+
+    /// About 5 orbits.
+    const STEPS: usize = 60_000;
+
+    #[test]
+    fn explicit_euler_gains_energy() {
+        assert!(energy_drift(Integrator::ExplicitEuler, STEPS) > 0.01);
+    }
+
+    #[test]
+    fn semi_implicit_euler_energy_is_bounded() {
+        assert!(energy_drift(Integrator::SemiImplicitEuler, STEPS).abs() < 1e-3);
+    }
+
+    #[test]
+    fn velocity_verlet_energy_is_bounded() {
+        assert!(energy_drift(Integrator::VelocityVerlet, STEPS).abs() < 1e-4);
+    }
+
+    #[test]
+    fn runge_kutta_4_energy_is_bounded() {
+        assert!(energy_drift(Integrator::RungeKutta4, STEPS).abs() < 1e-4);
+    }
+
+    #[test]
+    fn next_cycles_through_every_integrator() {
+        let mut integrator = Integrator::default();
+        for _ in 0..4 {
+            integrator = integrator.next();
+        }
+        assert_eq!(integrator, Integrator::default());
+    }
+}
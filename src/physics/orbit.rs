@@ -0,0 +1,138 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{GravityConfig, Mass, Velocity};
+use crate::time::TimeScale;
+
+/// Moves an entity "on rails" along a Keplerian orbit around it's parent,
+/// instead of integrating gravity. The periapsis is always in the +x
+/// direction of the parent, and the orbit is counter-clockwise.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+    pub parent: Entity,
+    pub semi_major_axis: f32,
+    /// Must be in `[0, 1)`. Hyperbolic orbits aren't supported.
+    pub eccentricity: f32,
+    /// The mean anomaly, in radians. Grows linearly with time.
+    pub mean_anomaly: f32,
+}
+
+impl Orbit {
+    /// The mean motion (average angular velocity) around a parent with this
+    /// gravitational parameter.
+    pub fn mean_motion(&self, parent_mu: f32) -> f32 {
+        let a = self.semi_major_axis;
+        (parent_mu / (a * a * a)).sqrt()
+    }
+
+    pub fn advance(&mut self, parent_mu: f32, delta: f32) {
+        self.mean_anomaly =
+            (self.mean_anomaly + self.mean_motion(parent_mu) * delta).rem_euclid(TAU);
+    }
+
+    pub fn eccentric_anomaly(&self) -> f32 {
+        solve_kepler(self.mean_anomaly, self.eccentricity)
+    }
+
+    /// The position relative to the parent. Doesn't depend on the parent's mass.
+    pub fn relative_position(&self) -> Vec2 {
+        let (sin, cos) = self.eccentric_anomaly().sin_cos();
+        let e = self.eccentricity;
+        self.semi_major_axis * Vec2::new(cos - e, (1.0 - e * e).sqrt() * sin)
+    }
+
+    pub fn relative_velocity(&self, parent_mu: f32) -> Vec2 {
+        let (sin, cos) = self.eccentric_anomaly().sin_cos();
+        let e = self.eccentricity;
+        let a = self.semi_major_axis;
+        let distance = a * (1.0 - e * cos);
+        let speed = (parent_mu * a).sqrt() / distance;
+        speed * Vec2::new(-sin, (1.0 - e * e).sqrt() * cos)
+    }
+}
+
+/// Solves Kepler's equation `M = E - e sin E` for the eccentric anomaly `E`
+/// using Newton's method.
+pub fn solve_kepler(mean_anomaly: f32, eccentricity: f32) -> f32 {
+    const MAX_ITERATIONS: usize = 16;
+    const TOLERANCE: f32 = 1e-6;
+
+    let mut eccentric_anomaly = if eccentricity < 0.8 {
+        mean_anomaly
+    } else {
+        std::f32::consts::PI
+    };
+    for _ in 0..MAX_ITERATIONS {
+        let (sin, cos) = eccentric_anomaly.sin_cos();
+        let step =
+            (eccentric_anomaly - eccentricity * sin - mean_anomaly) / (1.0 - eccentricity * cos);
+        eccentric_anomaly -= step;
+        if step.abs() < TOLERANCE {
+            break;
+        }
+    }
+    eccentric_anomaly
+}
+
+pub fn orbit_system(
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+    config: Res<GravityConfig>,
+    masses: Query<&Mass>,
+    roots: Query<(&Transform, Option<&Velocity>), Without<Orbit>>,
+    mut orbits: Query<(Entity, &mut Orbit, &mut Transform, &mut Velocity)>,
+) {
+    let delta = time_scale.delta_f32(&time);
+
+    // First advance all orbits, and find where everything is relative to it's parent.
+    let mut relative = HashMap::new();
+    for (entity, mut orbit, _, _) in orbits.iter_mut() {
+        let Ok(parent_mass) = masses.get(orbit.parent) else {
+            continue;
+        };
+        let parent_mu = config.gravitational_parameter(parent_mass.0);
+        orbit.advance(parent_mu, delta);
+        let state = (orbit.relative_position(), orbit.relative_velocity(parent_mu));
+        relative.insert(entity, (orbit.parent, state));
+    }
+
+    // Then resolve the absolute states, parents before children.
+    let mut absolute = HashMap::new();
+    for &entity in relative.keys() {
+        resolve_absolute_state(entity, &relative, &roots, &mut absolute);
+    }
+
+    for (entity, _, mut transform, mut velocity) in orbits.iter_mut() {
+        if let Some(&(position, new_velocity)) = absolute.get(&entity) {
+            transform.translation = position.extend(transform.translation.z);
+            velocity.0 = new_velocity;
+        }
+    }
+}
+
+fn resolve_absolute_state(
+    entity: Entity,
+    relative: &HashMap<Entity, (Entity, (Vec2, Vec2))>,
+    roots: &Query<(&Transform, Option<&Velocity>), Without<Orbit>>,
+    absolute: &mut HashMap<Entity, (Vec2, Vec2)>,
+) -> Option<(Vec2, Vec2)> {
+    if let Some(&state) = absolute.get(&entity) {
+        return Some(state);
+    }
+
+    let state = if let Some(&(parent, (position, velocity))) = relative.get(&entity) {
+        let (parent_position, parent_velocity) =
+            resolve_absolute_state(parent, relative, roots, absolute)?;
+        (parent_position + position, parent_velocity + velocity)
+    } else {
+        let (transform, velocity) = roots.get(entity).ok()?;
+        (
+            transform.translation.truncate(),
+            velocity.map_or(Vec2::ZERO, |v| v.0),
+        )
+    };
+
+    absolute.insert(entity, state);
+    Some(state)
+}
@@ -0,0 +1,167 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+/// The Keplerian elements of a two-body orbit, relative to the body being
+/// orbited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitalElements {
+    /// Negative for hyperbolic orbits, and infinite for parabolic ones.
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    /// The closest distance to the center of the orbited body.
+    pub periapsis: f32,
+    /// The furthest distance from the center of the orbited body. `None` if the
+    /// orbit isn't closed.
+    pub apoapsis: Option<f32>,
+    /// `None` if the orbit isn't closed.
+    pub period: Option<f32>,
+    /// The angle of the periapsis direction, in radians.
+    pub argument_of_periapsis: f32,
+    /// `1.0` for counter-clockwise orbits and `-1.0` for clockwise ones. This
+    /// is the sign of the inclination in 2D.
+    pub direction: f32,
+}
+
+impl OrbitalElements {
+    /// Finds the orbit of a body with this position and velocity, relative to
+    /// an orbited body with the gravitational parameter `mu`. Returns `None`
+    /// for degenerate states, like being at the center, or falling in a
+    /// straight line.
+    pub fn from_state(relative_position: Vec2, relative_velocity: Vec2, mu: f32) -> Option<Self> {
+        let distance = relative_position.length();
+        let speed_squared = relative_velocity.length_squared();
+        let angular_momentum = relative_position.perp_dot(relative_velocity);
+        if distance == 0.0 || mu <= 0.0 || angular_momentum == 0.0 {
+            return None;
+        }
+
+        let energy = speed_squared / 2.0 - mu / distance;
+        let semi_major_axis = -mu / (2.0 * energy);
+        let eccentricity_vector = ((speed_squared - mu / distance) * relative_position
+            - relative_position.dot(relative_velocity) * relative_velocity)
+            / mu;
+        let eccentricity = eccentricity_vector.length();
+
+        let semi_latus_rectum = angular_momentum * angular_momentum / mu;
+        let periapsis = semi_latus_rectum / (1.0 + eccentricity);
+        let (apoapsis, period) = if eccentricity < 1.0 {
+            let period = TAU * (semi_major_axis.powi(3) / mu).sqrt();
+            (Some(semi_latus_rectum / (1.0 - eccentricity)), Some(period))
+        } else {
+            (None, None)
+        };
+
+        // A circular orbit has no periapsis direction, so use the current one.
+        let periapsis_direction = if eccentricity > 1e-6 {
+            eccentricity_vector
+        } else {
+            relative_position
+        };
+
+        Some(Self {
+            semi_major_axis,
+            eccentricity,
+            periapsis,
+            apoapsis,
+            period,
+            argument_of_periapsis: periapsis_direction.y.atan2(periapsis_direction.x),
+            direction: angular_momentum.signum(),
+        })
+    }
+
+    pub fn periapsis_direction(&self) -> Vec2 {
+        Vec2::from_angle(self.argument_of_periapsis)
+    }
+
+    /// The periapsis relative to the center of the orbited body.
+    pub fn periapsis_position(&self) -> Vec2 {
+        self.periapsis_direction() * self.periapsis
+    }
+
+    /// The apoapsis relative to the center of the orbited body.
+    pub fn apoapsis_position(&self) -> Option<Vec2> {
+        self.apoapsis
+            .map(|apoapsis| -self.periapsis_direction() * apoapsis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    const MU: f32 = 1000.0;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-4 + 1e-4,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn circular() {
+        let speed = (MU / 100.0).sqrt();
+        let elements =
+            OrbitalElements::from_state(Vec2::new(100.0, 0.0), Vec2::new(0.0, speed), MU).unwrap();
+        assert_close(elements.eccentricity, 0.0);
+        assert_close(elements.semi_major_axis, 100.0);
+        assert_close(elements.periapsis, 100.0);
+        assert_close(elements.apoapsis.unwrap(), 100.0);
+        assert_close(elements.period.unwrap(), TAU * (100.0f32.powi(3) / MU).sqrt());
+        assert_eq!(elements.direction, 1.0);
+    }
+
+    #[test]
+    fn elliptical_from_periapsis() {
+        // Periapsis at 100 and apoapsis at 300.
+        let semi_major_axis = 200.0;
+        let speed = (MU * (2.0 / 100.0 - 1.0 / semi_major_axis)).sqrt();
+        let elements =
+            OrbitalElements::from_state(Vec2::new(0.0, 100.0), Vec2::new(-speed, 0.0), MU)
+                .unwrap();
+        assert_close(elements.eccentricity, 0.5);
+        assert_close(elements.semi_major_axis, semi_major_axis);
+        assert_close(elements.periapsis, 100.0);
+        assert_close(elements.apoapsis.unwrap(), 300.0);
+        assert_close(elements.argument_of_periapsis, FRAC_PI_2);
+        assert!(elements.apoapsis_position().unwrap().distance(Vec2::new(0.0, -300.0)) < 0.1);
+    }
+
+    #[test]
+    fn elliptical_from_apoapsis_clockwise() {
+        let semi_major_axis = 200.0;
+        let speed = (MU * (2.0 / 300.0 - 1.0 / semi_major_axis)).sqrt();
+        let elements =
+            OrbitalElements::from_state(Vec2::new(300.0, 0.0), Vec2::new(0.0, -speed), MU)
+                .unwrap();
+        assert_close(elements.eccentricity, 0.5);
+        assert_close(elements.semi_major_axis, semi_major_axis);
+        assert_close(elements.periapsis, 100.0);
+        assert_close(elements.apoapsis.unwrap(), 300.0);
+        assert!(elements.periapsis_position().distance(Vec2::new(-100.0, 0.0)) < 0.1);
+        assert_eq!(elements.direction, -1.0);
+    }
+
+    #[test]
+    fn hyperbolic() {
+        let speed = 1.5 * (2.0 * MU / 100.0).sqrt();
+        let elements =
+            OrbitalElements::from_state(Vec2::new(100.0, 0.0), Vec2::new(0.0, speed), MU).unwrap();
+        // e = r v² / mu - 1 at periapsis.
+        assert_close(elements.eccentricity, 100.0 * speed * speed / MU - 1.0);
+        assert!(elements.semi_major_axis < 0.0);
+        assert_close(elements.periapsis, 100.0);
+        assert_eq!(elements.apoapsis, None);
+        assert_eq!(elements.period, None);
+    }
+
+    #[test]
+    fn degenerate() {
+        let falling = OrbitalElements::from_state(Vec2::new(100.0, 0.0), Vec2::new(-5.0, 0.0), MU);
+        assert_eq!(falling, None);
+        let at_center = OrbitalElements::from_state(Vec2::ZERO, Vec2::new(0.0, 5.0), MU);
+        assert_eq!(at_center, None);
+    }
+}
@@ -0,0 +1,20 @@
+use bevy::prelude::*;
+
+/// A small xorshift generator for tests, so random bodies are the same every
+/// run.
+pub struct Random(pub u32);
+
+impl Random {
+    /// In `[0, 1)`.
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// In a `size` by `size` square around the origin.
+    pub fn point(&mut self, size: f32) -> Vec2 {
+        Vec2::new(self.next() - 0.5, self.next() - 0.5) * size
+    }
+}
@@ -0,0 +1,178 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+/// A shape that isn't a plain circle. An entity with a `Shape` still needs a
+/// `Circle` that bounds it, which the broad phase and swept tests use before
+/// checking the exact shape.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// A convex polygon, with the vertices counter-clockwise around the
+    /// entity's center.
+    Polygon(Vec<Vec2>),
+    /// A line along the local x axis from `-half_length` to `half_length`,
+    /// rounded by `radius`.
+    Capsule { half_length: f32, radius: f32 },
+}
+
+impl Shape {
+    /// The radius of the smallest circle around the entity's center that
+    /// holds the whole shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            Shape::Polygon(vertices) => vertices
+                .iter()
+                .map(|vertex| vertex.length())
+                .fold(0.0, f32::max),
+            Shape::Capsule {
+                half_length,
+                radius,
+            } => half_length + radius,
+        }
+    }
+
+    /// Points around the edge of the shape, counter-clockwise, for drawing.
+    pub fn outline(&self) -> Vec<Vec2> {
+        const CAP_SEGMENTS: usize = 8;
+
+        match self {
+            Shape::Polygon(vertices) => vertices.clone(),
+            &Shape::Capsule {
+                half_length,
+                radius,
+            } => {
+                let cap = |center: Vec2, start_angle: f32| {
+                    (0..=CAP_SEGMENTS).map(move |i| {
+                        let angle = start_angle + PI * i as f32 / CAP_SEGMENTS as f32;
+                        center + Vec2::from_angle(angle) * radius
+                    })
+                };
+                cap(Vec2::new(half_length, 0.0), -PI / 2.0)
+                    .chain(cap(Vec2::new(-half_length, 0.0), PI / 2.0))
+                    .collect()
+            }
+        }
+    }
+
+    /// The shape in world space, as a convex core rounded by a radius.
+    pub fn rounded_core(&self, transform: &Transform) -> RoundedCore {
+        let to_world = |point: Vec2| transform.transform_point(point.extend(0.0)).truncate();
+        match self {
+            Shape::Polygon(vertices) => RoundedCore {
+                vertices: vertices.iter().copied().map(to_world).collect(),
+                radius: 0.0,
+            },
+            &Shape::Capsule {
+                half_length,
+                radius,
+            } => RoundedCore {
+                vertices: vec![
+                    to_world(Vec2::new(-half_length, 0.0)),
+                    to_world(Vec2::new(half_length, 0.0)),
+                ],
+                radius,
+            },
+        }
+    }
+}
+
+/// A convex polygon, segment or point, grown by `radius` in every direction.
+/// Every shape is one of these, including circles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundedCore {
+    /// Counter-clockwise, in world space.
+    pub vertices: Vec<Vec2>,
+    pub radius: f32,
+}
+
+impl RoundedCore {
+    pub fn circle(center: Vec2, radius: f32) -> Self {
+        Self {
+            vertices: vec![center],
+            radius,
+        }
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        let (min, max) = self.vertices.iter().map(|vertex| vertex.dot(axis)).fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), projection| (min.min(projection), max.max(projection)),
+        );
+        (min - self.radius, max + self.radius)
+    }
+
+    /// The outward normals of the core's edges.
+    fn edge_normals(&self) -> impl Iterator<Item = Vec2> + '_ {
+        let count = self.vertices.len();
+        let edges = if count > 1 { count } else { 0 };
+        (0..edges).filter_map(move |i| {
+            let edge = self.vertices[(i + 1) % count] - self.vertices[i];
+            (-edge.perp()).try_normalize()
+        })
+    }
+
+    /// The point of the core that is furthest in `direction`.
+    fn support(&self, direction: Vec2) -> Vec2 {
+        self.vertices
+            .iter()
+            .copied()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap_or_default()
+    }
+}
+
+/// How two shapes overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeContact {
+    /// The normal pointing out of the collider.
+    pub normal: Vec2,
+    /// The point on the collider's surface.
+    pub point: Vec2,
+    /// How far the shape has to move along `normal` to stop overlapping.
+    pub depth: f32,
+}
+
+/// Checks if `shape` overlaps `collider` with the separating axis theorem. If
+/// it does, returns the shortest way to push `shape` out.
+pub fn shape_contact(shape: &RoundedCore, collider: &RoundedCore) -> Option<ShapeContact> {
+    // Edge normals find the separation between polygons, and the directions
+    // between vertices find it between rounded corners.
+    let vertex_axes = shape.vertices.iter().flat_map(|&a| {
+        collider
+            .vertices
+            .iter()
+            .filter_map(move |&b| (a - b).try_normalize())
+    });
+    let axes = shape
+        .edge_normals()
+        .chain(collider.edge_normals())
+        .chain(vertex_axes);
+
+    let mut best: Option<(Vec2, f32)> = None;
+    for axis in axes {
+        let (shape_min, shape_max) = shape.project(axis);
+        let (collider_min, collider_max) = collider.project(axis);
+        // How far the shape has to move along either way of the axis to get out.
+        let forward = collider_max - shape_min;
+        let backward = shape_max - collider_min;
+        if forward <= 0.0 || backward <= 0.0 {
+            return None;
+        }
+        let (normal, depth) = if forward <= backward {
+            (axis, forward)
+        } else {
+            (-axis, backward)
+        };
+        if best.is_none_or(|(_, best_depth)| depth < best_depth) {
+            best = Some((normal, depth));
+        }
+    }
+
+    let (normal, depth) = best?;
+    let deepest = shape.support(-normal) - normal * shape.radius;
+    Some(ShapeContact {
+        normal,
+        point: deepest + normal * depth,
+        depth,
+    })
+}
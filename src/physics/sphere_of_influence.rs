@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use super::{
+    gravity_sources, gravity_system, AffectedByGravity, GravityConfig, GravitySourceQuery,
+    GravitySourceState,
+};
+
+pub struct SphereOfInfluencePlugin;
+
+impl Plugin for SphereOfInfluencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SoiTransitionEvent>().add_systems(
+            FixedUpdate,
+            sphere_of_influence_system
+                .before(gravity_system)
+                .in_set(super::PhysicsSet::PhysicsSet),
+        );
+    }
+}
+
+/// The gravity source whose sphere of influence an entity is in.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DominantBody(pub Option<Entity>);
+
+/// Sent when an entity leaves one sphere of influence and enters another.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoiTransitionEvent {
+    pub entity: Entity,
+    pub from: Option<Entity>,
+    pub to: Option<Entity>,
+}
+
+/// The Laplace radius of a body orbiting a heavier parent at this distance.
+pub fn sphere_of_influence_radius(semi_major_axis: f32, mass: f32, parent_mass: f32) -> f32 {
+    semi_major_axis * (mass / parent_mass).powf(0.4)
+}
+
+/// The index of the source whose sphere of influence `point` is in. When the
+/// point is in multiple spheres, the smallest one wins. Sources that don't
+/// orbit anything have infinite spheres, and between those the one that pulls
+/// the hardest wins.
+pub fn dominant_source(
+    config: &GravityConfig,
+    affectors: &[GravitySourceState],
+    exclude: Entity,
+    point: Vec2,
+) -> Option<usize> {
+    let pull = |affector: &GravitySourceState| {
+        config.acceleration(affector.position - point, affector.mass)
+            .map_or(0.0, |acceleration| acceleration.length_squared())
+    };
+
+    affectors
+        .iter()
+        .enumerate()
+        .filter(|(_, affector)| affector.entity != exclude)
+        .filter(|(_, affector)| affector.position.distance(point) <= affector.sphere_of_influence)
+        .min_by(|(_, a), (_, b)| {
+            a.sphere_of_influence
+                .total_cmp(&b.sphere_of_influence)
+                .then_with(|| pull(b).total_cmp(&pull(a)))
+        })
+        .map(|(index, _)| index)
+}
+
+fn sphere_of_influence_system(
+    config: Res<GravityConfig>,
+    sources: GravitySourceQuery,
+    mut affected: Query<(Entity, &Transform, Option<&mut DominantBody>), With<AffectedByGravity>>,
+    mut events: EventWriter<SoiTransitionEvent>,
+    mut commands: Commands,
+) {
+    let affectors = gravity_sources(&sources);
+    for (entity, transform, dominant_body) in affected.iter_mut() {
+        let point = transform.translation.truncate();
+        let dominant = dominant_source(&config, &affectors, entity, point)
+            .map(|index| affectors[index].entity);
+        match dominant_body {
+            Some(mut dominant_body) if dominant_body.0 != dominant => {
+                events.send(SoiTransitionEvent {
+                    entity,
+                    from: dominant_body.0,
+                    to: dominant,
+                });
+                dominant_body.0 = dominant;
+            }
+            Some(_) => {}
+            None => {
+                commands.entity(entity).insert(DominantBody(dominant));
+            }
+        }
+    }
+}
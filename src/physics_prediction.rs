@@ -1,110 +1,1108 @@
 use crate::{
-    physics::{get_gravity_acceleration, GravitySource, Mass, Velocity},
+    physics::{
+        body_contact, gravity_sources, step_body, AffectedByGravity, Circle, Collider,
+        CollisionBody, GravityConfig, GravityField, GravityMode, GravitySource,
+        GravitySourceQuery, GravitySourceState, Integrator, Orbit, Shape, Velocity,
+    },
+    floating_origin::OriginShifted,
     ship::Ship,
+    time::TimeScale,
 };
-use bevy::{prelude::*, sprite::Mesh2dHandle};
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    sprite::{Anchor, Mesh2dHandle},
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
 
 pub struct PhysicsPredictionPlugin;
 
 impl Plugin for PhysicsPredictionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_physics_prediction)
-            .add_systems(Update, physics_prediction_system);
+        app.init_resource::<PredictionBudget>()
+            .add_systems(Startup, setup_physics_prediction)
+            .add_systems(
+                Update,
+                (
+                    ship_prediction_system.before(update_predictions),
+                    update_predictions,
+                    prediction_mesh_system.after(update_predictions),
+                    impact_marker_system.after(update_predictions),
+                ),
+            );
     }
 }
 
+/// The prediction of the ship's path.
+#[derive(Component)]
+pub struct PhysicsPrediction;
+
+/// Marks where the predicted path hits something.
 #[derive(Component)]
-struct PhysicsPrediction;
+struct ImpactMarker;
+
+/// Limits how much work a single prediction can do.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct PredictionBudget {
+    pub max_points: usize,
+    pub max_distance: f32,
+}
+
+impl Default for PredictionBudget {
+    fn default() -> Self {
+        Self {
+            max_points: 10000,
+            max_distance: 1e5,
+        }
+    }
+}
+
+/// A predicted path, drawn as a line by the mesh on the same entity. Whoever
+/// owns it sets `input` every frame, and the path is computed on the
+/// `AsyncComputeTaskPool` and reused for as long as the input follows it.
+#[derive(Component, Default)]
+pub struct Prediction {
+    /// What to predict. `None` hides the path.
+    pub input: Option<PredictionInput>,
+    /// The latest finished prediction, and what it was made from.
+    cached: Option<(PredictionInput, PredictedPath)>,
+    /// The point on the cached path that the input is currently at.
+    start: usize,
+    /// Set when the mesh needs to be regenerated.
+    redraw: bool,
+    /// The running prediction, and how much the origin moved since it started.
+    task: Option<(Task<(PredictionInput, PredictedPath)>, Vec2)>,
+}
+
+impl Prediction {
+    /// Moves the cached path along with everything else, when the origin moves.
+    fn shift(&mut self, offset: Vec2) {
+        if let Some((input, path)) = &mut self.cached {
+            input.shift(offset);
+            path.shift(offset);
+        }
+        if let Some((_, shift_in_flight)) = &mut self.task {
+            *shift_in_flight += offset;
+        }
+    }
+
+    /// The path from where the input currently is.
+    pub fn points(&self) -> &[PathPoint] {
+        self.cached
+            .as_ref()
+            .map_or(&[], |(_, path)| &path.points[self.start..])
+    }
+
+    /// Where the path hits something, with the time counted from now.
+    pub fn impact(&self) -> Option<Impact> {
+        let (_, path) = self.cached.as_ref()?;
+        let now = self.points().first()?.time;
+        path.impact.map(|impact| Impact {
+            time: impact.time - now,
+            ..impact
+        })
+    }
+
+    /// The state `time` seconds from now, interpolated between points.
+    pub fn sample(&self, time: f32) -> Option<PathPoint> {
+        let points = self.points();
+        let now = points.first()?.time;
+        let time = now + time;
+        let index = points.partition_point(|point| point.time <= time);
+        if index == 0 || index == points.len() {
+            return None;
+        }
+
+        let (a, b) = (&points[index - 1], &points[index]);
+        let t = (time - a.time) / (b.time - a.time);
+        Some(PathPoint {
+            position: a.position.lerp(b.position, t),
+            velocity: a.velocity.lerp(b.velocity, t),
+            time: time - now,
+        })
+    }
+}
+
+/// Everything a prediction depends on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredictionInput {
+    ship: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    radius: f32,
+    affectors: Vec<GravitySourceState>,
+    /// The affectors that are on rails, parents before their children.
+    orbits: Vec<OrbitingSource>,
+    /// The affectors that are moved by gravity (n-body).
+    falling: Vec<FallingSource>,
+    colliders: Vec<PredictedCollider>,
+    integrator: Integrator,
+    mode: GravityMode,
+    gravity: GravityConfig,
+    /// The simulation time of one physics tick.
+    delta: f32,
+    budget: PredictionBudget,
+}
+
+impl PredictionInput {
+    fn shift(&mut self, offset: Vec2) {
+        self.position -= offset;
+        for affector in &mut self.affectors {
+            affector.position -= offset;
+        }
+        for collider in &mut self.colliders {
+            collider.transform.translation -= offset.extend(0.0);
+            collider.previous_position -= offset;
+        }
+    }
+
+    /// Is everything except for the ship's state the same? Sources and
+    /// colliders that move during the prediction only have to move the same
+    /// way, not be in the same place. As long as the ship follows the path,
+    /// sources on rails and colliders are where the path expects them to be.
+    /// Sources that fall are checked against the path with `sources_follow`.
+    fn same_environment(&self, other: &Self) -> bool {
+        let same_affectors = self.affectors.len() == other.affectors.len()
+            && (0..self.affectors.len()).all(|index| {
+                let (a, b) = (&self.affectors[index], &other.affectors[index]);
+                if self.source_moves(index) {
+                    GravitySourceState {
+                        position: b.position,
+                        ..*a
+                    } == *b
+                } else {
+                    a == b
+                }
+            });
+        let same_orbits = self.orbits.len() == other.orbits.len()
+            && self.orbits.iter().zip(&other.orbits).all(|(a, b)| a.same_orbit(b));
+        let same_falling = self.falling.len() == other.falling.len()
+            && self.falling.iter().zip(&other.falling).all(|(a, b)| a.source == b.source);
+
+        self.ship == other.ship
+            && self.radius == other.radius
+            && same_affectors
+            && same_orbits
+            && same_falling
+            && self.colliders.len() == other.colliders.len()
+            && self.colliders.iter().zip(&other.colliders).all(|(a, b)| a.same_collider(b))
+            && self.integrator == other.integrator
+            && self.mode == other.mode
+            && self.gravity == other.gravity
+            && self.delta == other.delta
+            && self.budget == other.budget
+    }
+
+    /// Does the affector with this index move during the prediction?
+    fn source_moves(&self, index: usize) -> bool {
+        self.orbits.iter().any(|orbiting| orbiting.source == index)
+            || self.falling.iter().any(|falling| falling.source == index)
+    }
+}
+
+/// A collider the path can hit.
+#[derive(Debug, Clone, PartialEq)]
+struct PredictedCollider {
+    entity: Entity,
+    transform: Transform,
+    /// Where it was a tick ago, for swept collisions.
+    previous_position: Vec2,
+    radius: f32,
+    shape: Option<Shape>,
+    motion: ColliderMotion,
+}
+
+impl PredictedCollider {
+    /// Is this the same collider, moving the same way, regardless of where it
+    /// is if it moves?
+    fn same_collider(&self, other: &Self) -> bool {
+        let same_motion = match (self.motion, other.motion) {
+            (ColliderMotion::Falling { .. }, ColliderMotion::Falling { .. }) => true,
+            (a, b) => a == b,
+        };
+        self.entity == other.entity
+            && self.transform.rotation == other.transform.rotation
+            && self.radius == other.radius
+            && self.shape == other.shape
+            && same_motion
+            && (self.motion != ColliderMotion::Static
+                || self.transform.translation == other.transform.translation)
+    }
+}
+
+/// How a collider moves during the prediction, like it does in the simulation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColliderMotion {
+    /// Stays where it is.
+    Static,
+    /// Follows the affector with this index, which is on rails or falls.
+    WithSource(usize),
+    /// Is moved by gravity, like a station in orbit.
+    Falling { velocity: Vec2 },
+}
+
+/// A gravity source that is moved by gravity, like `gravity_system` does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FallingSource {
+    /// The index of the source in the affectors.
+    source: usize,
+    velocity: Vec2,
+}
+
+/// A gravity source on rails, which the prediction moves along it's orbit like
+/// `orbit_system` does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OrbitingSource {
+    /// The index of the source in the affectors.
+    source: usize,
+    /// The index of it's parent in the affectors.
+    parent: usize,
+    orbit: Orbit,
+    parent_mu: f32,
+}
+
+impl OrbitingSource {
+    /// Is this the same orbit, regardless of where on it the source is?
+    fn same_orbit(&self, other: &Self) -> bool {
+        self.source == other.source
+            && self.parent == other.parent
+            && self.orbit.parent == other.orbit.parent
+            && self.orbit.semi_major_axis == other.orbit.semi_major_axis
+            && self.orbit.eccentricity == other.orbit.eccentricity
+            && self.parent_mu == other.parent_mu
+    }
+}
+
+/// Everything needed to make a `PredictionInput`, other than the ship's state.
+#[derive(SystemParam)]
+pub struct PredictionContext<'w, 's> {
+    affectors: GravitySourceQuery<'w, 's>,
+    colliders: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Transform,
+            &'static Circle,
+            Option<&'static Shape>,
+            Option<&'static Velocity>,
+            Option<&'static AffectedByGravity>,
+        ),
+        With<Collider>,
+    >,
+    falling: Query<
+        'w,
+        's,
+        (Entity, &'static Velocity),
+        (With<GravitySource>, With<AffectedByGravity>),
+    >,
+    integrator: Res<'w, Integrator>,
+    mode: Res<'w, GravityMode>,
+    gravity: Res<'w, GravityConfig>,
+    time: Res<'w, FixedTime>,
+    time_scale: Res<'w, TimeScale>,
+    budget: Res<'w, PredictionBudget>,
+}
+
+impl PredictionContext<'_, '_> {
+    /// The input for predicting where `ship` goes from the given state.
+    pub fn input(
+        &self,
+        ship: Entity,
+        position: Vec2,
+        velocity: Vec2,
+        radius: f32,
+    ) -> PredictionInput {
+        let affectors = gravity_sources(&self.affectors);
+        let orbits = self.orbiting_sources(&affectors);
+        let falling = self.falling_sources(&affectors, &orbits);
+        PredictionInput {
+            ship,
+            position,
+            velocity,
+            radius,
+            colliders: self.predicted_colliders(&affectors, &orbits, &falling),
+            orbits,
+            falling,
+            affectors,
+            integrator: *self.integrator,
+            mode: *self.mode,
+            gravity: *self.gravity,
+            delta: self.time_scale.delta_f32(&self.time),
+            budget: *self.budget,
+        }
+    }
+
+    /// The sources moved by gravity. Sources on rails aren't, even if they
+    /// are affected by gravity, since `orbit_system` has the last word.
+    fn falling_sources(
+        &self,
+        affectors: &[GravitySourceState],
+        orbits: &[OrbitingSource],
+    ) -> Vec<FallingSource> {
+        self.falling
+            .iter()
+            .filter_map(|(entity, velocity)| {
+                let source = affectors.iter().position(|affector| affector.entity == entity)?;
+                let on_rails = orbits.iter().any(|orbiting| orbiting.source == source);
+                (!on_rails).then_some(FallingSource {
+                    source,
+                    velocity: velocity.0,
+                })
+            })
+            .collect()
+    }
+
+    fn predicted_colliders(
+        &self,
+        affectors: &[GravitySourceState],
+        orbits: &[OrbitingSource],
+        falling: &[FallingSource],
+    ) -> Vec<PredictedCollider> {
+        self.colliders
+            .iter()
+            .map(|(entity, transform, circle, shape, velocity, affected)| {
+                let source = affectors.iter().position(|affector| affector.entity == entity);
+                let motion = match (source, velocity, affected) {
+                    (Some(source), ..)
+                        if orbits.iter().any(|o| o.source == source)
+                            || falling.iter().any(|f| f.source == source) =>
+                    {
+                        ColliderMotion::WithSource(source)
+                    }
+                    (None, Some(velocity), Some(_)) => ColliderMotion::Falling {
+                        velocity: velocity.0,
+                    },
+                    _ => ColliderMotion::Static,
+                };
+                PredictedCollider {
+                    entity,
+                    transform: *transform,
+                    previous_position: transform.translation.truncate(),
+                    radius: circle.radius,
+                    shape: shape.cloned(),
+                    motion,
+                }
+            })
+            .collect()
+    }
+
+    fn orbiting_sources(&self, affectors: &[GravitySourceState]) -> Vec<OrbitingSource> {
+        let index_of = |entity| affectors.iter().position(|affector| affector.entity == entity);
+        let orbits = self
+            .affectors
+            .iter()
+            .filter_map(|(entity, _, _, orbit)| {
+                let orbit = *orbit?;
+                let parent = index_of(orbit.parent)?;
+                Some(OrbitingSource {
+                    source: index_of(entity)?,
+                    parent,
+                    orbit,
+                    parent_mu: self.gravity.gravitational_parameter(affectors[parent].mass),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Parents have to move before their children.
+        let depth = |mut index: usize| {
+            let mut depth = 0;
+            while let Some(orbiting) = orbits.iter().find(|orbiting| orbiting.source == index) {
+                depth += 1;
+                index = orbiting.parent;
+                if depth > orbits.len() {
+                    break;
+                }
+            }
+            depth
+        };
+        let mut sorted = orbits.clone();
+        sorted.sort_by_cached_key(|orbiting| depth(orbiting.source));
+        sorted
+    }
+}
 
 #[derive(Bundle)]
 struct PhysicsPredictionBundle {
     physics_prediction: PhysicsPrediction,
+    prediction: Prediction,
     mesh: ColorMesh2dBundle,
 }
 
+const IMPACT_MARKER_RADIUS: f32 = 4.0;
+
 fn setup_physics_prediction(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     commands.spawn(PhysicsPredictionBundle {
         physics_prediction: PhysicsPrediction,
+        prediction: default(),
         mesh: ColorMesh2dBundle {
             mesh: meshes.add(Mesh::from(shape::Circle::new(1.0))).into(),
             material: materials.add(Color::rgb(0.5, 0.1, 0.1).into()),
             ..default()
         },
     });
+
+    commands
+        .spawn((
+            ImpactMarker,
+            ColorMesh2dBundle {
+                mesh: meshes
+                    .add(Mesh::from(shape::Circle::new(IMPACT_MARKER_RADIUS)))
+                    .into(),
+                material: materials.add(Color::rgb(0.9, 0.2, 0.1).into()),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(Text2dBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("pixeboy.ttf"),
+                        font_size: 20.,
+                        color: Color::rgb(0.9, 0.2, 0.1),
+                    },
+                ),
+                text_anchor: Anchor::BottomCenter,
+                transform: Transform::from_xyz(0.0, IMPACT_MARKER_RADIUS * 2.0, 1.0),
+                ..default()
+            });
+        });
+}
+
+fn ship_prediction_system(
+    ship_query: Query<(Entity, &Transform, &Velocity, Option<&Circle>), With<Ship>>,
+    mut predictions: Query<&mut Prediction, With<PhysicsPrediction>>,
+    context: PredictionContext,
+) {
+    let mut prediction = predictions.single_mut();
+    let Ok((ship, ship_tr, ship_vel, ship_circle)) = ship_query.get_single() else {
+        // No level is loaded.
+        prediction.input = None;
+        return;
+    };
+    prediction.input = Some(context.input(
+        ship,
+        ship_tr.translation.truncate(),
+        ship_vel.0,
+        ship_circle.map_or(0.0, |c| c.radius),
+    ));
+}
+
+pub fn update_predictions(
+    mut predictions: Query<&mut Prediction>,
+    mut origin_shifts: EventReader<OriginShifted>,
+) {
+    let offset: Vec2 = origin_shifts.iter().map(|shift| shift.offset).sum();
+    for mut prediction in predictions.iter_mut() {
+        if offset != Vec2::ZERO {
+            prediction.shift(offset);
+        }
+        update_prediction(&mut prediction);
+    }
 }
 
-fn physics_prediction_system(
-    mut mesh_query: Query<(&Mesh2dHandle, &mut Transform), With<PhysicsPrediction>>,
-    ship_query: Query<(&Transform, &Velocity), (With<Ship>, Without<PhysicsPrediction>)>,
-    affectors: Query<(&Transform, &Mass), (With<GravitySource>, Without<PhysicsPrediction>)>,
+fn update_prediction(prediction: &mut Prediction) {
+    let finished = prediction
+        .task
+        .as_mut()
+        .and_then(|(task, _)| future::block_on(future::poll_once(task)));
+    if let Some((mut input, mut path)) = finished {
+        // The origin might have moved while the prediction was running.
+        let offset = prediction.task.take().map_or(Vec2::ZERO, |(_, offset)| offset);
+        input.shift(offset);
+        path.shift(offset);
+        prediction.cached = Some((input, path));
+        prediction.start = 0;
+        prediction.redraw = true;
+    }
+
+    let Some(input) = prediction.input.clone() else {
+        if prediction.cached.is_some() {
+            prediction.cached = None;
+            prediction.redraw = true;
+        }
+        return;
+    };
+
+    // If the input is still following the cached path, just move along it.
+    let start_on_cached_path = prediction
+        .cached
+        .as_ref()
+        .filter(|(cached_input, _)| cached_input.same_environment(&input))
+        .and_then(|(_, path)| {
+            find_on_path(
+                &path.points,
+                prediction.start,
+                input.position,
+                input.velocity,
+            )
+            .filter(|&start| path.sources_follow(start, &input))
+        });
+    if let Some(start) = start_on_cached_path {
+        if start != prediction.start {
+            prediction.start = start;
+            prediction.redraw = true;
+        }
+        return;
+    }
+
+    // Otherwise, start a new prediction, unless one is already running.
+    if prediction.task.is_some() {
+        return;
+    }
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let path = generate_path(&input);
+        (input, path)
+    });
+    prediction.task = Some((task, Vec2::ZERO));
+}
+
+/// How far the ship, or a source that falls, can be from the path and still
+/// be following it.
+const POSITION_TOLERANCE: f32 = 1.0;
+
+/// Finds the point the ship is at on the path, searching forward from `from`.
+/// Returns `None` if the ship has strayed from the path.
+fn find_on_path(
+    points: &[PathPoint],
+    from: usize,
+    position: Vec2,
+    velocity: Vec2,
+) -> Option<usize> {
+    const SEARCH_WINDOW: usize = 64;
+    const VELOCITY_TOLERANCE: f32 = 0.5;
+
+    let (index, distance, path_velocity) = points
+        .windows(2)
+        .enumerate()
+        .skip(from)
+        .take(SEARCH_WINDOW)
+        .map(|(index, segment)| {
+            let (a, b) = (&segment[0], &segment[1]);
+            let along = b.position - a.position;
+            let t = if along == Vec2::ZERO {
+                0.0
+            } else {
+                ((position - a.position).dot(along) / along.length_squared()).clamp(0.0, 1.0)
+            };
+            let closest = a.position + along * t;
+            let path_velocity = a.velocity.lerp(b.velocity, t);
+            (index, closest.distance(position), path_velocity)
+        })
+        .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))?;
+
+    (distance <= POSITION_TOLERANCE && path_velocity.distance(velocity) <= VELOCITY_TOLERANCE)
+        .then_some(index)
+}
+
+fn prediction_mesh_system(
+    mut predictions: Query<(
+        &mut Prediction,
+        &Mesh2dHandle,
+        &mut Transform,
+        &mut Visibility,
+    )>,
     mut meshes: ResMut<Assets<Mesh>>,
 ) {
-    let (ship_tr, ship_vel) = ship_query.single();
-    let path = generate_path(ship_tr.translation.truncate(), ship_vel.0, &affectors);
+    for (mut prediction, mesh_handle, mut transform, mut visibility) in predictions.iter_mut() {
+        if !prediction.redraw {
+            continue;
+        }
+        prediction.redraw = false;
+
+        let points = prediction.points();
+        let Some(first) = points.first() else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+        transform.translation = first.position.extend(transform.translation.z);
+        let mesh = meshes.get_mut(&mesh_handle.0).unwrap();
+        *mesh = generate_mesh_from_path(points);
+    }
+}
+
+fn impact_marker_system(
+    prediction: Query<&Prediction, With<PhysicsPrediction>>,
+    mut impact_marker: Query<(&mut Transform, &mut Visibility, &Children), With<ImpactMarker>>,
+    mut texts: Query<&mut Text>,
+    camera: Query<&OrthographicProjection, With<Camera>>,
+) {
+    let (mut marker_transform, mut marker_visibility, children) = impact_marker.single_mut();
+    if let Some(impact) = prediction.single().impact() {
+        *marker_visibility = Visibility::Visible;
+        // Keep the marker the same size on screen, regardless of zoom.
+        *marker_transform = Transform::from_translation(impact.point.extend(2.0))
+            .with_scale(Vec3::splat(camera.single().scale));
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = format!("Impact in {:.1}s", impact.time);
+            }
+        }
+    } else {
+        *marker_visibility = Visibility::Hidden;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PathPoint {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    /// Seconds from the start of the path, in simulation time.
+    pub time: f32,
+}
+
+#[derive(Debug, Clone)]
+struct PredictedPath {
+    points: Vec<PathPoint>,
+    /// Where the sources that fall are at each point, in the same order as
+    /// `PredictionInput::falling`.
+    sources: Vec<Vec<Vec2>>,
+    impact: Option<Impact>,
+}
+
+impl PredictedPath {
+    fn shift(&mut self, offset: Vec2) {
+        for point in &mut self.points {
+            point.position -= offset;
+        }
+        for position in self.sources.iter_mut().flatten() {
+            *position -= offset;
+        }
+        if let Some(impact) = &mut self.impact {
+            impact.point -= offset;
+        }
+    }
 
-    let (mesh_handle, mut transform) = mesh_query.single_mut();
-    let mesh = meshes.get_mut(&mesh_handle.0).unwrap();
-    *mesh = generate_mesh_from_path(&path);
-    transform.translation = path[0].extend(0.0);
+    /// Are the sources that fall where the path expected them to be, when
+    /// the ship is between the points `index` and `index + 1`?
+    fn sources_follow(&self, index: usize, input: &PredictionInput) -> bool {
+        let (Some(from), Some(to)) = (self.sources.get(index), self.sources.get(index + 1)) else {
+            return false;
+        };
+        input.falling.iter().enumerate().all(|(k, falling)| {
+            let position = input.affectors[falling.source].position;
+            let along = to[k] - from[k];
+            let t = if along == Vec2::ZERO {
+                0.0
+            } else {
+                ((position - from[k]).dot(along) / along.length_squared()).clamp(0.0, 1.0)
+            };
+            (from[k] + along * t).distance(position) <= POSITION_TOLERANCE
+        })
+    }
 }
 
-fn generate_path<'a>(
-    ship_pos: Vec2,
-    ship_vel: Vec2,
-    affectors: impl IntoIterator<Item = (&'a Transform, &'a Mass)> + Copy,
-) -> Vec<Vec2> {
+#[derive(Debug, Clone, Copy)]
+pub struct Impact {
+    pub point: Vec2,
+    /// Seconds from the start of the path, in simulation time.
+    pub time: f32,
+}
+
+fn generate_path(input: &PredictionInput) -> PredictedPath {
     let mut path = Vec::new();
-    let mut pos = ship_pos;
-    let mut vel = ship_vel;
+    let mut source_positions = Vec::new();
+    let mut pos = input.position;
+    let mut vel = input.velocity;
     let mut distance_travelled = 0.0;
-    let mut iter = 0;
+    let mut ticks = 0;
+    let mut sources = PredictedSources::new(input);
+    // Always at least the starting point, so the path is never empty.
+    let max_points = input.budget.max_points.max(1);
+
+    while distance_travelled < input.budget.max_distance && path.len() < max_points {
+        path.push(PathPoint {
+            position: pos,
+            velocity: vel,
+            time: ticks as f32 * input.delta,
+        });
+        source_positions.push(sources.falling_positions());
+        let impact = generate_next_path_point(
+            input,
+            &mut sources,
+            &mut pos,
+            &mut vel,
+            &mut distance_travelled,
+            &mut ticks,
+        );
 
-    while distance_travelled < 1e5 && iter < 10000 {
-        path.push(pos);
-        generate_next_path_point(&mut pos, &mut vel, &mut distance_travelled, affectors);
-        iter += 1;
+        if let Some(point) = impact {
+            let time = ticks as f32 * input.delta;
+            path.push(PathPoint {
+                position: pos,
+                velocity: vel,
+                time,
+            });
+            source_positions.push(sources.falling_positions());
+            return PredictedPath {
+                points: path,
+                sources: source_positions,
+                impact: Some(Impact { point, time }),
+            };
+        }
     }
 
-    path
+    PredictedPath {
+        points: path,
+        sources: source_positions,
+        impact: None,
+    }
 }
 
-fn generate_next_path_point<'a>(
+/// The gravity sources during a prediction. Sources on rails move along their
+/// orbits, sources that fall are moved by gravity, and the rest stay where
+/// they are.
+struct PredictedSources<'a> {
+    input: &'a PredictionInput,
+    affectors: Vec<GravitySourceState>,
+    orbits: Vec<OrbitingSource>,
+    falling: Vec<FallingSource>,
+    colliders: Vec<PredictedCollider>,
+    /// If no source moves, they are prepared once.
+    static_field: Option<GravityField<'a>>,
+}
+
+impl<'a> PredictedSources<'a> {
+    fn new(input: &'a PredictionInput) -> Self {
+        let static_field = (input.orbits.is_empty() && input.falling.is_empty())
+            .then(|| GravityField::new(input.mode, input.gravity, &input.affectors));
+        Self {
+            input,
+            affectors: input.affectors.clone(),
+            orbits: input.orbits.clone(),
+            falling: input.falling.clone(),
+            colliders: input.colliders.clone(),
+            static_field,
+        }
+    }
+
+    fn falling_positions(&self) -> Vec<Vec2> {
+        self.falling
+            .iter()
+            .map(|falling| self.affectors[falling.source].position)
+            .collect()
+    }
+
+    /// Advances the ship, the sources and the colliders that fall by one
+    /// physics tick, all in the same field, and then the sources on rails.
+    /// Colliders that are sources go along with them. This is the same order
+    /// as `gravity_system` and `orbit_system`.
+    fn step(&mut self, position: Vec2, velocity: Vec2) -> (Vec2, Vec2) {
+        let input = self.input;
+        let (state, falling) = {
+            let moving_field;
+            let field = match &self.static_field {
+                Some(field) => field,
+                None => {
+                    moving_field = GravityField::new(input.mode, input.gravity, &self.affectors);
+                    &moving_field
+                }
+            };
+            let step = |entity, position, velocity| {
+                step_body(input.integrator, field, entity, position, velocity, input.delta)
+            };
+
+            for collider in &mut self.colliders {
+                collider.previous_position = collider.transform.translation.truncate();
+                if let ColliderMotion::Falling { velocity } = &mut collider.motion {
+                    let position;
+                    (position, *velocity) =
+                        step(collider.entity, collider.previous_position, *velocity);
+                    let z = collider.transform.translation.z;
+                    collider.transform.translation = position.extend(z);
+                }
+            }
+            let falling = self
+                .falling
+                .iter()
+                .map(|falling| {
+                    let affector = &self.affectors[falling.source];
+                    step(affector.entity, affector.position, falling.velocity)
+                })
+                .collect::<Vec<_>>();
+            (step(input.ship, position, velocity), falling)
+        };
+
+        for (source, (position, velocity)) in self.falling.iter_mut().zip(falling) {
+            self.affectors[source.source].position = position;
+            source.velocity = velocity;
+        }
+        for orbiting in &mut self.orbits {
+            orbiting.orbit.advance(orbiting.parent_mu, input.delta);
+            self.affectors[orbiting.source].position =
+                self.affectors[orbiting.parent].position + orbiting.orbit.relative_position();
+        }
+        for collider in &mut self.colliders {
+            if let ColliderMotion::WithSource(source) = collider.motion {
+                let z = collider.transform.translation.z;
+                collider.transform.translation = self.affectors[source].position.extend(z);
+            }
+        }
+        state
+    }
+}
+
+/// How many physics ticks each point of the path is apart.
+const TICKS_PER_POINT: i32 = 10;
+
+/// Advances the path by `TICKS_PER_POINT` physics ticks, exactly like the
+/// simulation would. Returns the impact point if the path hit a collider.
+fn generate_next_path_point(
+    input: &PredictionInput,
+    sources: &mut PredictedSources,
     pos: &mut Vec2,
     vel: &mut Vec2,
     distance_travelled: &mut f32,
-    affectors: impl IntoIterator<Item = (&'a Transform, &'a Mass)> + Copy,
-) {
-    const DELTA: f32 = 1.0 / 60.0;
-    const STEPS: i32 = 10;
-
-    for _ in 0..STEPS {
-        // Update the velocity
-        for (affector_transform, affector_mass) in affectors {
-            let relative_position = affector_transform.translation.truncate() - *pos;
-            if let Ok(change_in_vel) =
-                get_gravity_acceleration(relative_position, affector_mass.0)
-            {
-                *vel += change_in_vel * DELTA;
+    ticks: &mut i32,
+) -> Option<Vec2> {
+    for _ in 0..TICKS_PER_POINT {
+        let previous_pos = *pos;
+        (*pos, *vel) = sources.step(*pos, *vel);
+        *distance_travelled += vel.length() * input.delta;
+        *ticks += 1;
+
+        // Stop at the first thing we hit, checking it's exact shape like
+        // `collision_detection` does.
+        let transform = Transform::from_translation(pos.extend(0.0));
+        let body = CollisionBody {
+            transform: &transform,
+            previous_position: previous_pos,
+            radius: input.radius,
+            shape: None,
+        };
+        for collider in &sources.colliders {
+            let collider = CollisionBody {
+                transform: &collider.transform,
+                previous_position: collider.previous_position,
+                radius: collider.radius,
+                shape: collider.shape.as_ref(),
+            };
+            if let Some(contact) = body_contact(&body, &collider) {
+                *pos = contact.point + contact.normal * input.radius;
+                return Some(contact.point);
             }
         }
-
-        // Update the position
-        *pos += *vel * DELTA;
-        *distance_travelled += vel.length() * DELTA;
     }
+
+    None
 }
 
-fn generate_mesh_from_path(path: &Vec<Vec2>) -> Mesh {
+fn generate_mesh_from_path(path: &[PathPoint]) -> Mesh {
     let mut mesh = Mesh::new(bevy::render::render_resource::PrimitiveTopology::LineStrip);
     let mut vertices = Vec::new();
-    for pos in path {
-        let pos_relative_to_first = *pos - path[0];
+    for point in path {
+        let pos_relative_to_first = point.position - path[0].position;
         vertices.push(pos_relative_to_first.extend(0.0));
     }
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
     mesh
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::physics::{gravity_system, orbit_system, AffectedByGravity, GravitySource, Mass};
+
+    /// Runs the real physics systems for `points` points worth of ticks, and
+    /// checks that the ship, and a planet that falls around the big one, go
+    /// exactly where `generate_path` said they would.
+    fn assert_prediction_matches_simulation(mode: GravityMode, points: usize) {
+        let mut world = World::new();
+        world.insert_resource(FixedTime::new_from_secs(1.0 / 60.0));
+        world.insert_resource(TimeScale::default());
+        world.insert_resource(Integrator::default());
+        world.insert_resource(mode);
+        world.insert_resource(GravityConfig::default());
+        world.insert_resource(PredictionBudget {
+            max_points: points,
+            max_distance: f32::INFINITY,
+        });
+
+        let planet = world
+            .spawn((GravitySource, Mass(1e6), Transform::default()))
+            .id();
+        world.spawn((
+            GravitySource,
+            Mass(1e3),
+            Orbit {
+                parent: planet,
+                semi_major_axis: 1000.0,
+                eccentricity: 0.2,
+                mean_anomaly: 0.0,
+            },
+            Transform::from_xyz(800.0, 0.0, 0.0),
+            Velocity::default(),
+        ));
+        let falling_planet = world
+            .spawn((
+                GravitySource,
+                AffectedByGravity,
+                Mass(1e4),
+                Transform::from_xyz(-1500.0, 0.0, 0.0),
+                Velocity(Vec2::new(0.0, 25.0)),
+            ))
+            .id();
+        let ship_position = Vec2::new(0.0, 700.0);
+        let ship_velocity = Vec2::new(-30.0, 0.0);
+        let ship = world
+            .spawn((
+                AffectedByGravity,
+                Transform::from_translation(ship_position.extend(0.0)),
+                Velocity(ship_velocity),
+            ))
+            .id();
+
+        let mut context = SystemState::<PredictionContext>::new(&mut world);
+        let input = context
+            .get(&world)
+            .input(ship, ship_position, ship_velocity, 0.0);
+        let path = generate_path(&input);
+        assert_eq!(path.points.len(), points);
+        assert_eq!(input.falling.len(), 1);
+        assert!(path.impact.is_none());
+
+        let mut schedule = Schedule::new();
+        schedule.add_systems((gravity_system, orbit_system.after(gravity_system)));
+        for (index, point) in path.points.iter().enumerate() {
+            if index > 0 {
+                for _ in 0..TICKS_PER_POINT {
+                    schedule.run(&mut world);
+                }
+            }
+            let position = world.get::<Transform>(ship).unwrap().translation.truncate();
+            let velocity = world.get::<Velocity>(ship).unwrap().0;
+            assert!(
+                point.position.distance(position) < 1e-3,
+                "{mode:?}: point {index} at {} but the ship is at {position}",
+                point.position
+            );
+            assert!(point.velocity.distance(velocity) < 1e-3);
+            let planet_position = world
+                .get::<Transform>(falling_planet)
+                .unwrap()
+                .translation
+                .truncate();
+            assert!(path.sources[index][0].distance(planet_position) < 1e-3);
+
+            // Still following the path, so it doesn't have to be predicted
+            // again.
+            if index + 1 < path.points.len() {
+                let now = context.get(&world).input(ship, position, velocity, 0.0);
+                assert!(input.same_environment(&now), "{mode:?}: point {index}");
+                let start = find_on_path(&path.points, index, position, velocity);
+                assert!(
+                    start.is_some_and(|start| path.sources_follow(start, &now)),
+                    "{mode:?}: point {index}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn prediction_matches_simulation() {
+        assert_prediction_matches_simulation(GravityMode::Full, 100);
+    }
+
+    #[test]
+    fn prediction_matches_simulation_with_patched_conics() {
+        assert_prediction_matches_simulation(GravityMode::PatchedConics, 100);
+    }
+
+    #[test]
+    fn prediction_matches_simulation_with_barnes_hut() {
+        let mode = GravityMode::BarnesHut { opening_angle: 0.5 };
+        assert_prediction_matches_simulation(mode, 100);
+    }
+
+    #[test]
+    fn empty_budget_still_has_the_starting_point() {
+        let mut world = World::new();
+        world.insert_resource(FixedTime::new_from_secs(1.0 / 60.0));
+        world.insert_resource(TimeScale::default());
+        world.insert_resource(Integrator::default());
+        world.insert_resource(GravityMode::default());
+        world.insert_resource(GravityConfig::default());
+        world.insert_resource(PredictionBudget {
+            max_points: 0,
+            max_distance: 1e5,
+        });
+        let ship = world.spawn_empty().id();
+
+        let mut context = SystemState::<PredictionContext>::new(&mut world);
+        let input = context.get(&world).input(ship, Vec2::ZERO, Vec2::X, 0.0);
+        assert_eq!(generate_path(&input).points.len(), 1);
+    }
+
+    /// Predicts a ship flying right along `y` past a flat box, whose bounding
+    /// circle reaches much higher than the box does. The box falls, but with
+    /// nothing to fall to, it just keeps it's velocity.
+    fn impact_flying_past_box(y: f32, box_position: Vec2, box_velocity: Vec2) -> Option<Impact> {
+        let mut world = World::new();
+        world.insert_resource(FixedTime::new_from_secs(1.0 / 60.0));
+        world.insert_resource(TimeScale::default());
+        world.insert_resource(Integrator::default());
+        world.insert_resource(GravityMode::default());
+        world.insert_resource(GravityConfig::default());
+        world.insert_resource(PredictionBudget {
+            max_points: 30,
+            max_distance: f32::INFINITY,
+        });
+        let shape = Shape::Polygon(vec![
+            Vec2::new(-30.0, -6.0),
+            Vec2::new(30.0, -6.0),
+            Vec2::new(30.0, 6.0),
+            Vec2::new(-30.0, 6.0),
+        ]);
+        world.spawn((
+            Transform::from_translation(box_position.extend(0.0)),
+            Circle {
+                radius: shape.bounding_radius(),
+            },
+            shape,
+            Collider,
+            Velocity(box_velocity),
+            AffectedByGravity,
+        ));
+        let ship = world.spawn_empty().id();
+
+        let mut context = SystemState::<PredictionContext>::new(&mut world);
+        let input = context
+            .get(&world)
+            .input(ship, Vec2::new(0.0, y), Vec2::new(60.0, 0.0), 1.0);
+        generate_path(&input).impact
+    }
+
+    #[test]
+    fn path_hits_the_exact_shape() {
+        let box_position = Vec2::new(100.0, 0.0);
+        let impact = impact_flying_past_box(0.0, box_position, Vec2::ZERO)
+            .expect("the path goes through the box");
+        assert!((impact.point.x - 70.0).abs() < 1.1, "{impact:?}");
+
+        // Inside the bounding circle, but well above the box.
+        assert!(impact_flying_past_box(20.0, box_position, Vec2::ZERO).is_none());
+    }
+
+    #[test]
+    fn path_hits_colliders_where_they_will_be() {
+        // The box only gets to the path as the ship does, at about 1.7s.
+        let box_position = Vec2::new(100.0, -100.0);
+        let impact = impact_flying_past_box(0.0, box_position, Vec2::new(0.0, 60.0))
+            .expect("the box moves into the path");
+        assert!((1.4..1.8).contains(&impact.time), "{impact:?}");
+
+        // Where the box is now, the path misses it.
+        assert!(impact_flying_past_box(0.0, box_position, Vec2::ZERO).is_none());
+    }
+}
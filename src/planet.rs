@@ -1,11 +1,17 @@
 use bevy::prelude::*;
 
-use crate::physics::{GravitySource, Mass, Collider, Circle};
+use bevy::render::{mesh::Indices, render_resource::PrimitiveTopology};
+
+use crate::{
+    physics::{GravitySource, Mass, Collider, Circle, PhysicsMaterial, Shape},
+    ship::Ship,
+};
 
 pub struct PlanetPlugin;
 
 impl Plugin for PlanetPlugin {
-    fn build(&self, _app: &mut App) {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, hull_mesh_system);
     }
 }
 
@@ -20,10 +26,11 @@ pub struct PlanetBundle {
     sprite: SpriteBundle,
     collider: Collider,
     circle: Circle,
+    material: PhysicsMaterial,
 }
 
 impl PlanetBundle {
-    pub fn new(asset_server: &AssetServer, radius: f32, position: Vec2) -> Self {
+    pub fn new(asset_server: &AssetServer, radius: f32, mass: f32, position: Vec2) -> Self {
         let texture = asset_server.load("planet.png");
         Self {
             sprite: SpriteBundle {
@@ -35,11 +42,50 @@ impl PlanetBundle {
                 transform: Transform::from_xyz(position.x, position.y, 0.0),
                 ..default()
             },
-            mass: Mass(radius * radius * radius),
+            mass: Mass(mass),
             planet: Planet,
             gravity_source: GravitySource,
             collider: default(),
             circle: Circle { radius },
+            material: default(),
         }
     }
 }
+
+/// Draws planets, asteroids and stations with a hull as their shape, instead
+/// of as a round sprite. The sprite's color tints the hull.
+fn hull_mesh_system(
+    mut commands: Commands,
+    mut bodies: Query<(Entity, &Shape, &mut Sprite), (Added<Shape>, Without<Ship>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (entity, shape, mut sprite) in bodies.iter_mut() {
+        let color = Color::rgb(0.55, 0.5, 0.45) * sprite.color.as_rgba_f32();
+        sprite.color = Color::NONE;
+        let mesh = meshes.add(generate_mesh_from_outline(&shape.outline()));
+        let material = materials.add(color.into());
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn(ColorMesh2dBundle {
+                mesh: mesh.into(),
+                material,
+                ..default()
+            });
+        });
+    }
+}
+
+/// A triangle fan, so the outline must be convex.
+fn generate_mesh_from_outline(outline: &[Vec2]) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    let vertices = outline
+        .iter()
+        .map(|point| point.extend(0.0))
+        .collect::<Vec<_>>();
+    let indices = (1..outline.len().saturating_sub(1) as u32)
+        .flat_map(|i| [0, i, i + 1])
+        .collect();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
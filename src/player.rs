@@ -2,8 +2,8 @@ use bevy::prelude::*;
 
 use crate::{
     get_input_dir,
-    physics::get_gravity_acceleration,
-    physics::{AffectedByGravity, Circle, Collision, GravitySource, Mass, Velocity},
+    level::LevelUnloadedEvent,
+    physics::{AffectedByGravity, BroadPhase, Circle, Collision, GravityConfig, Mass, Velocity},
     time::TimeScale,
 };
 
@@ -13,7 +13,8 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup_player)
             .add_systems(Update, input_system)
-            .add_systems(Update, rotate);
+            .add_systems(Update, rotate)
+            .add_systems(Update, reset_player_on_unload.before(input_system));
     }
 }
 
@@ -40,6 +41,21 @@ fn setup_player(mut commands: Commands, asset_server: Res<AssetServer>) {
     ));
 }
 
+/// The player isn't part of the level, so it's put back where it started
+/// whenever a level is unloaded.
+fn reset_player_on_unload(
+    mut unloaded: EventReader<LevelUnloadedEvent>,
+    mut player: Query<(&mut Transform, &mut Velocity), With<Player>>,
+) {
+    if unloaded.iter().last().is_none() {
+        return;
+    }
+    for (mut transform, mut velocity) in player.iter_mut() {
+        *transform = Transform::default();
+        *velocity = Velocity::default();
+    }
+}
+
 const PLAYER_SPEED: f32 = 50.0;
 fn input_system(
     mut player: Query<&mut Transform, With<Player>>,
@@ -54,12 +70,12 @@ fn input_system(
 
 fn rotate(
     mut player: Query<&mut Transform, With<Player>>,
-    gravity_sources: Query<(&Transform, &Mass), (With<GravitySource>, Without<Player>)>,
+    broad_phase: Res<BroadPhase>,
+    config: Res<GravityConfig>,
 ) {
     let mut player = player.single_mut();
-    let gravity_sources = gravity_sources.iter();
     let strongest_affector_relative_point =
-        strongest_affector(gravity_sources, player.translation.truncate());
+        strongest_affector(&broad_phase, &config, player.translation.truncate());
 
     if let Some(dir) = strongest_affector_relative_point {
         let angle = dir.y.atan2(dir.x);
@@ -67,18 +83,12 @@ fn rotate(
     }
 }
 
-fn strongest_affector<'a>(
-    iter: impl IntoIterator<Item = (&'a Transform, &'a Mass)>,
+fn strongest_affector(
+    broad_phase: &BroadPhase,
+    config: &GravityConfig,
     point: Vec2,
 ) -> Option<Vec2> {
-    iter.into_iter()
-        .flat_map(|(transform, mass)| {
-            let dir = transform.translation.truncate() - point;
-            get_gravity_acceleration(dir, mass.0)
-                .ok()
-                .map(Vec2::length_squared)
-                .map(|accel| (transform, accel))
-        })
-        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
-        .map(|(transform, _)| transform.translation.truncate() - point)
+    broad_phase
+        .strongest_pull(config, point)
+        .map(|(affector, _)| affector.position - point)
 }
@@ -1,9 +1,12 @@
-use std::f32::consts::{PI, TAU};
-
 use crate::{
+    autopilot::Autopilot,
+    contact::Wrecked,
     get_input_dir,
-    physics::{AffectedByGravity, Circle, Collision, Mass, Velocity},
-    planet::Planet, time::TimeScale, camera::CameraTarget,
+    physics::{
+        AffectedByGravity, AngularVelocity, Atmosphere, BroadPhase, Circle, Collision, Drag, Mass,
+        MomentOfInertia, PhysicsMaterial, PhysicsSet, Shape, Velocity,
+    },
+    time::TimeScale, camera::CameraTarget,
 };
 use bevy::prelude::*;
 
@@ -11,8 +14,19 @@ pub struct ShipPlugin;
 
 impl Plugin for ShipPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_ship)
-            .add_systems(Update, (input_system, set_sky_color_by_planet_distance));
+        app.add_systems(
+            Update,
+            (
+                input_system,
+                sas_input_system,
+                set_sky_color_by_planet_distance,
+                reentry_effect_system,
+            ),
+        )
+        .add_systems(
+            FixedUpdate,
+            apply_controls_system.before(PhysicsSet::PhysicsSet),
+        );
     }
 }
 
@@ -20,22 +34,56 @@ impl Plugin for ShipPlugin {
 pub struct Ship {
     pub max_fuel: f32,
     pub fuel: f32,
+    pub hull: f32,
 }
 
+/// How much hull a ship starts with, and can be repaired to.
+pub const MAX_HULL: f32 = 100.0;
+
 impl Default for Ship {
     fn default() -> Self {
         Self {
             fuel: 100.0,
             max_fuel: 100.0,
+            hull: MAX_HULL,
         }
     }
 }
 
+/// What the ship is told to do, by the player or by the autopilot.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShipControls {
+    /// The direction to turn towards.
+    pub direction: Option<Vec2>,
+    /// How hard to thrust, from 0 to 1.
+    pub throttle: f32,
+}
+
+/// Stability assist. While on, the ship uses it's RCS to stop spinning when
+/// it isn't told to turn.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sas(pub bool);
+
+impl Default for Sas {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
 #[derive(Bundle)]
 struct ShipBundle {
     ship: Ship,
+    controls: ShipControls,
+    sas: Sas,
+    autopilot: Autopilot,
     velocity: Velocity,
+    angular_velocity: AngularVelocity,
     mass: Mass,
+    moment_of_inertia: MomentOfInertia,
+    circle: Circle,
+    shape: Shape,
+    material: PhysicsMaterial,
+    drag: Drag,
     affected_by_gravity: AffectedByGravity,
     collision: Collision,
     sprite: SpriteBundle,
@@ -46,8 +94,28 @@ impl Default for ShipBundle {
     fn default() -> Self {
         Self {
             mass: Mass(1.0),
+            moment_of_inertia: MomentOfInertia::disk(1.0, SHIP_RADIUS),
+            circle: Circle {
+                radius: SHIP_RADIUS,
+            },
+            // Pointy at the nose, and flat at the bottom to land on.
+            shape: Shape::Polygon(vec![
+                Vec2::new(SHIP_RADIUS, 0.0),
+                Vec2::new(-4.0, 3.0),
+                Vec2::new(-4.0, -3.0),
+            ]),
+            // Only used when bumping into other ships and debris.
+            material: PhysicsMaterial {
+                friction: 0.5,
+                restitution: 0.3,
+            },
+            drag: Drag::new(0.1),
             ship: default(),
+            controls: default(),
+            sas: default(),
+            autopilot: default(),
             velocity: default(),
+            angular_velocity: default(),
             affected_by_gravity: default(),
             collision: default(),
             sprite: SpriteBundle {
@@ -62,98 +130,216 @@ impl Default for ShipBundle {
     }
 }
 
-fn setup_ship(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(ShipBundle {
-        sprite: SpriteBundle {
-            texture: asset_server.load("ship.png"),
-            ..ShipBundle::default().sprite
-        },
-        ..default()
-    });
+/// Glows around the ship when the air heats it up.
+#[derive(Component)]
+struct ReentryGlow;
+
+/// The deceleration from drag at which the reentry glow is brightest.
+const REENTRY_GLOW_DECELERATION: f32 = 50.0;
+
+/// Spawns the player's ship. The level does this, where it says the ship
+/// starts. `fuel` is clamped to the tank, and a full tank if not set.
+pub fn spawn_ship(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    transform: Transform,
+    velocity: Vec2,
+    fuel: Option<f32>,
+) -> Entity {
+    let texture = asset_server.load("ship.png");
+    let ship = Ship::default();
+    commands
+        .spawn(ShipBundle {
+            ship: Ship {
+                fuel: fuel.map_or(ship.max_fuel, |fuel| fuel.clamp(0.0, ship.max_fuel)),
+                ..ship
+            },
+            velocity: Velocity(velocity),
+            sprite: SpriteBundle {
+                texture: texture.clone(),
+                transform,
+                ..ShipBundle::default().sprite
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                ReentryGlow,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::NONE,
+                        custom_size: Some(Vec2::splat(16.0)),
+                        ..default()
+                    },
+                    texture,
+                    transform: Transform::from_xyz(0.0, 0.0, -0.1),
+                    ..default()
+                },
+            ));
+        })
+        .id()
 }
 
+const SHIP_RADIUS: f32 = 5.0;
+/// The fastest the RCS will spin the ship, in radians per second.
 const MAX_ROTATION_SPEED: f32 = 3.0;
-const MAX_VELOCITY_CHANGE: f32 = 100.0;
+/// How much torque the RCS thrusters have.
+const MAX_RCS_TORQUE: f32 = 75.0;
+/// How much a second of full thrust changes the ship's velocity.
+pub const MAX_VELOCITY_CHANGE: f32 = 100.0;
+
+/// How much fuel it takes to change the ship's velocity by `delta_v`.
+pub fn fuel_for_delta_v(delta_v: f32) -> f32 {
+    delta_v / MAX_VELOCITY_CHANGE
+}
+
+/// The fuel used, and the change in velocity, from thrusting for `delta`
+/// seconds.
+pub fn thrust(transform: &Transform, ship: &Ship, throttle: f32, delta: f32) -> (f32, Vec2) {
+    let fuel_used = (throttle.clamp(0.0, 1.0) * delta).min(ship.fuel);
+    let delta_v = transform.right().truncate() * fuel_used * MAX_VELOCITY_CHANGE;
+    (fuel_used, delta_v)
+}
+
 fn input_system(
-    mut ships: Query<(&mut Velocity, &mut Transform, &mut Ship)>,
+    mut ships: Query<(&mut ShipControls, &mut Autopilot), Without<Wrecked>>,
     input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-    time_scale: Res<TimeScale>,
 ) {
-    // Only move the ship if the alt key is not held down
-    if input.pressed(KeyCode::AltLeft) || input.pressed(KeyCode::AltRight) {
-        return;
-    }
-
-    let dir = get_input_dir(&input);
-    for (mut velocity, mut transform, mut ship) in ships.iter_mut() {
-        if input.pressed(KeyCode::Space) {
-            let fuel_used = time_scale.delta_f32(&time).min(ship.fuel);
-            ship.fuel -= fuel_used;
-            velocity.0 += transform.right().truncate() * fuel_used * MAX_VELOCITY_CHANGE;
+    // Only move the ship if the alt key is not held down, and let go of the
+    // controls while it is.
+    let alt = input.pressed(KeyCode::AltLeft) || input.pressed(KeyCode::AltRight);
+    let dir = if alt { Vec2::ZERO } else { get_input_dir(&input) };
+    let thrusting = !alt && input.pressed(KeyCode::Space);
+    for (mut controls, mut autopilot) in ships.iter_mut() {
+        // Any manual input takes control away from the autopilot.
+        if dir != Vec2::ZERO || thrusting {
+            *autopilot = Autopilot::Off;
+        }
+        if *autopilot != Autopilot::Off {
+            continue;
         }
 
-        if dir != Vec2::ZERO {
-            let max_rotation = MAX_ROTATION_SPEED * time_scale.delta_f32(&time);
-            let target_angle = dir.y.atan2(dir.x).rem_euclid(TAU);
-            let current_angle = transform.rotation.to_axis_angle().1.rem_euclid(TAU);
-            let next_angle = move_angle_towards(current_angle, target_angle, max_rotation);
+        *controls = ShipControls {
+            direction: (dir != Vec2::ZERO).then_some(dir),
+            throttle: if thrusting { 1.0 } else { 0.0 },
+        };
+    }
+}
 
-            transform.rotation = Quat::from_rotation_z(next_angle.rem_euclid(TAU));
+fn sas_input_system(mut ships: Query<&mut Sas>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::V) {
+        for mut sas in ships.iter_mut() {
+            sas.0 = !sas.0;
+            info!("SAS: {}", if sas.0 { "On" } else { "Off" });
         }
     }
 }
 
-fn move_angle_towards(angle: f32, target: f32, step: f32) -> f32 {
-    assert!(step >= 0.0);
+/// Applies the controls every physics tick, so they work the same at any time
+/// scale.
+pub fn apply_controls_system(
+    mut ships: Query<
+        (
+            &ShipControls,
+            &Sas,
+            &mut Velocity,
+            &mut AngularVelocity,
+            &MomentOfInertia,
+            &Transform,
+            &mut Ship,
+        ),
+        Without<Wrecked>,
+    >,
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+) {
+    let delta = time_scale.delta_f32(&time);
+    for (controls, sas, mut velocity, mut angular_velocity, inertia, transform, mut ship) in
+        ships.iter_mut()
+    {
+        let (fuel_used, delta_v) = thrust(transform, &ship, controls.throttle, delta);
+        ship.fuel -= fuel_used;
+        velocity.0 += delta_v;
 
-    let mut diff = (target - angle).rem_euclid(TAU);
-    if diff > PI {
-        diff -= TAU;
-    }
-    if diff.abs() < step {
-        target
-    } else {
-        angle + step * diff.signum()
+        let target_angular_velocity = match controls.direction {
+            Some(dir) => {
+                let max_acceleration = MAX_RCS_TORQUE / inertia.0;
+                let current_dir = transform.right().truncate();
+                rotation_speed_towards(current_dir.angle_between(dir), max_acceleration, delta)
+            }
+            None if sas.0 => 0.0,
+            None => continue,
+        };
+
+        let max_change = MAX_RCS_TORQUE / inertia.0 * delta;
+        let change = (target_angular_velocity - angular_velocity.0).clamp(-max_change, max_change);
+        angular_velocity.0 += change;
     }
 }
 
+/// The angular velocity to turn `angle` radians with, so that the ship can
+/// still stop in time with `max_acceleration`, without overshooting in the
+/// next `delta` seconds.
+fn rotation_speed_towards(angle: f32, max_acceleration: f32, delta: f32) -> f32 {
+    let speed = (2.0 * max_acceleration * angle.abs())
+        .sqrt()
+        .min(angle.abs() / delta)
+        .min(MAX_ROTATION_SPEED);
+    speed * angle.signum()
+}
+
 fn set_sky_color_by_planet_distance(
     ship: Query<&Transform, With<Ship>>,
-    planets: Query<(&Transform, &Circle), With<Planet>>,
+    broad_phase: Res<BroadPhase>,
+    atmospheres: Query<&Atmosphere>,
     mut sky: ResMut<ClearColor>,
 ) {
-    let ship_transform = ship.single();
+    let Ok(ship_transform) = ship.get_single() else {
+        return;
+    };
     let ship_position = ship_transform.translation.truncate();
 
-    let (min_distance, radius) = if let Some((planet_transform, planet_circle)) =
-        planets.iter().min_by(|(tr_1, _), (tr_2, _)| {
-            let dist_1 = tr_1
-                .translation
-                .truncate()
-                .distance_squared(ship_position);
-            let dist_2 = tr_2
-                .translation
-                .truncate()
-                .distance_squared(ship_position);
-            dist_1.partial_cmp(&dist_2).unwrap()
-        }) {
-        let distance_squared =
-            ship_position.distance_squared(planet_transform.translation.truncate());
-        let radius = planet_circle.radius;
-        ((distance_squared - radius * radius).max(0.).sqrt(), radius)
-    } else {
-        (1.0, 1.0)
+    let Some((planet, distance)) = broad_phase.nearest_source(ship_position) else {
+        *sky = ClearColor(space_color());
+        return;
     };
+    let atmosphere = atmospheres.get(planet.entity).ok();
 
-    *sky = ClearColor(get_sky_color(min_distance, radius));
+    *sky = ClearColor(get_sky_color(distance, planet.radius, atmosphere));
 }
 
-fn get_sky_color(distance: f32, radius: f32) -> Color {
-    let color_1: Color = Color::hex("88b4db").unwrap();
-    let color_2: Color = Color::hex("150e19").unwrap();
+fn space_color() -> Color {
+    Color::hex("150e19").unwrap()
+}
+
+/// The sky at `distance` from the center of a planet. Planets without an
+/// atmosphere fade out over one radius, like before atmospheres existed.
+fn get_sky_color(distance: f32, radius: f32, atmosphere: Option<&Atmosphere>) -> Color {
+    let (sky_color, t) = match atmosphere {
+        Some(atmosphere) => {
+            let altitude = (distance - radius).max(0.0);
+            (atmosphere.sky_color, altitude / atmosphere.height)
+        }
+        None => {
+            let tangent = (distance * distance - radius * radius).max(0.0).sqrt();
+            (Color::hex("88b4db").unwrap(), tangent / radius)
+        }
+    };
+    let t = (t + 0.05).clamp(0.0, 1.0);
 
-    let t = (distance / radius + 0.05).min(1.0).max(0.0);
+    Vec4::lerp(sky_color.into(), space_color().into(), t).into()
+}
 
-    Vec4::lerp(color_1.into(), color_2.into(), t).into()
+fn reentry_effect_system(
+    ships: Query<(&Drag, &Children), With<Ship>>,
+    mut glows: Query<&mut Sprite, With<ReentryGlow>>,
+) {
+    for (drag, children) in ships.iter() {
+        let heat = (drag.deceleration / REENTRY_GLOW_DECELERATION).min(1.0);
+        for &child in children.iter() {
+            if let Ok(mut sprite) = glows.get_mut(child) {
+                sprite.color = Color::rgba(1.0, 0.5, 0.1, heat);
+            }
+        }
+    }
 }
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+
+use crate::{
+    contact::{SurfaceContact, Wrecked},
+    physics::{Circle, Collider, Mass, PhysicsMaterial, PhysicsSet, Shape},
+    ship::{Ship, MAX_HULL},
+    time::TimeScale,
+};
+
+pub struct StationPlugin;
+
+impl Plugin for StationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, station_service_system.after(PhysicsSet::PhysicsSet));
+    }
+}
+
+/// How much fuel a docked ship gets per second.
+const STATION_REFUEL_RATE: f32 = 20.0;
+/// How much hull a docked ship gets back per second.
+const STATION_REPAIR_RATE: f32 = 10.0;
+const STATION_HALF_SIZE: Vec2 = Vec2::new(30.0, 6.0);
+
+/// A platform the ship can dock with by landing on it. Docked ships are
+/// refueled and repaired.
+#[derive(Component, Default)]
+pub struct Station;
+
+#[derive(Bundle)]
+pub struct StationBundle {
+    station: Station,
+    mass: Mass,
+    sprite: SpriteBundle,
+    collider: Collider,
+    circle: Circle,
+    shape: Shape,
+    material: PhysicsMaterial,
+}
+
+impl StationBundle {
+    pub fn new(position: Vec2) -> Self {
+        let shape = Shape::Polygon(vec![
+            Vec2::new(-STATION_HALF_SIZE.x, -STATION_HALF_SIZE.y),
+            Vec2::new(STATION_HALF_SIZE.x, -STATION_HALF_SIZE.y),
+            STATION_HALF_SIZE,
+            Vec2::new(-STATION_HALF_SIZE.x, STATION_HALF_SIZE.y),
+        ]);
+        Self {
+            sprite: SpriteBundle {
+                // Only tints the hull, see `planet::hull_mesh_system`.
+                sprite: Sprite {
+                    color: Color::rgb(0.6, 0.7, 0.9),
+                    ..default()
+                },
+                transform: Transform::from_xyz(position.x, position.y, 0.0),
+                ..default()
+            },
+            mass: Mass(100.0),
+            station: Station,
+            collider: default(),
+            circle: Circle {
+                radius: shape.bounding_radius(),
+            },
+            shape,
+            // Grippy and soft, to make docking easy.
+            material: PhysicsMaterial {
+                friction: 1.0,
+                restitution: 0.0,
+            },
+        }
+    }
+}
+
+/// Refuels and repairs ships that are resting on a station.
+fn station_service_system(
+    time: Res<FixedTime>,
+    time_scale: Res<TimeScale>,
+    stations: Query<(), With<Station>>,
+    mut ships: Query<(&mut Ship, &SurfaceContact), Without<Wrecked>>,
+) {
+    let delta = time_scale.delta_f32(&time);
+    for (mut ship, contact) in ships.iter_mut() {
+        if !contact.0.iter().any(|&body| stations.contains(body)) {
+            continue;
+        }
+        ship.fuel = (ship.fuel + STATION_REFUEL_RATE * delta).min(ship.max_fuel);
+        ship.hull = (ship.hull + STATION_REPAIR_RATE * delta).min(MAX_HULL);
+    }
+}